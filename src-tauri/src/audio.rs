@@ -1,77 +1,170 @@
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use cpal::{SampleRate, StreamConfig};
-use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use cpal::{
+    FromSample, SampleFormat, SampleRate, SizedSample, StreamConfig, SupportedStreamConfig,
+};
+use ringbuf::traits::{Consumer, Producer, Split};
+use ringbuf::{HeapCons, HeapProd, HeapRb};
+use serde::{Deserialize, Serialize};
+use std::any::Any;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
 use std::sync::{Arc, Mutex};
-use std::thread;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::resample::resample;
+use crate::vad::{VadConfig, VadEvent, VoiceActivityDetector};
+
+/// Capture ring size; the consumer drains it every `DRAIN_INTERVAL`
+const RING_SECONDS: usize = 2;
+const DRAIN_INTERVAL: Duration = Duration::from_millis(10);
+/// Samples moved out of the ring at a time
+const CHUNK_SAMPLES: usize = 4096;
+
+/// The input device a recording ended up using
+#[derive(Debug, Clone)]
+pub struct InputDeviceChoice {
+    pub name: String,
+    /// Why the preferred device was not used, if we fell back to the default
+    pub fallback_reason: Option<String>,
+}
+
+/// What the device actually delivers, before conversion to mono f32
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaptureFormat {
+    pub device: String,
+    /// cpal's name for it, e.g. `i16` or `f32`
+    pub sample_format: String,
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+/// Filled in by one recording's capture thread, read by its recorder
+#[derive(Default)]
+struct Captured {
+    /// Mono samples at the device rate
+    samples: Mutex<Vec<f32>>,
+    /// Samples dropped because the ring buffer was full
+    overruns: AtomicU64,
+    /// Why the stream died while recording
+    error: Mutex<Option<String>>,
+}
 
-// Global static for recording management
-static RECORDING_FLAG: AtomicBool = AtomicBool::new(false);
-static STREAM_READY: AtomicBool = AtomicBool::new(false);
-static SAMPLE_RATE: AtomicU32 = AtomicU32::new(16000);
-static SAMPLES: once_cell::sync::Lazy<Arc<Mutex<Vec<f32>>>> =
-    once_cell::sync::Lazy::new(|| Arc::new(Mutex::new(Vec::new())));
+/// A recording in progress. The thread owns the stream, since cpal streams
+/// can't move between threads; it closes it when `stop` fires or is dropped.
+struct Capture {
+    stop: Sender<()>,
+    thread: JoinHandle<()>,
+}
+
+impl Capture {
+    /// Close the stream and wait until everything it captured is in `Captured`
+    fn finish(self) {
+        let _ = self.stop.send(());
+        if self.thread.join().is_err() {
+            eprintln!("Recording thread panicked");
+        }
+    }
+}
 
+/// Records from one input device. Every recorder has its own stream and
+/// buffers, so several can run at once.
 pub struct AudioRecorder {
+    captured: Arc<Captured>,
+    capture: Option<Capture>,
     sample_rate: u32,
+    format: Option<CaptureFormat>,
+    vad_events: Option<Receiver<VadEvent>>,
 }
 
 impl AudioRecorder {
     pub fn new() -> Self {
-        Self { sample_rate: 16000 }
+        Self {
+            captured: Arc::default(),
+            capture: None,
+            sample_rate: 16000,
+            format: None,
+            vad_events: None,
+        }
     }
 
-    pub fn start_recording(&mut self) -> Result<(), String> {
-        if RECORDING_FLAG.load(Ordering::SeqCst) {
+    /// Start capturing from `preferred_device` (matched by name), falling back
+    /// to the system default when it is missing and `allow_fallback` is set.
+    /// Returns once the stream is running, or with the reason it couldn't start.
+    pub fn start_recording(
+        &mut self,
+        preferred_device: Option<&str>,
+        allow_fallback: bool,
+    ) -> Result<InputDeviceChoice, String> {
+        let host = cpal::default_host();
+        let (device, choice) = select_input_device(&host, preferred_device, allow_fallback)?;
+        self.start_with(Box::new(CpalSource(device)))?;
+        Ok(choice)
+    }
+
+    /// Start capturing from `source` on a thread of its own
+    fn start_with(&mut self, source: Box<dyn InputSource>) -> Result<(), String> {
+        if self.capture.is_some() {
             return Err("Already recording".to_string());
         }
 
-        // Clear previous samples
-        if let Ok(mut samples) = SAMPLES.lock() {
-            samples.clear();
-        }
+        let captured = Arc::new(Captured::default());
+        let (vad_tx, vad_rx) = mpsc::channel();
+        let (started_tx, started_rx) = mpsc::channel();
+        let (stop_tx, stop_rx) = mpsc::channel();
 
-        STREAM_READY.store(false, Ordering::SeqCst);
-        RECORDING_FLAG.store(true, Ordering::SeqCst);
+        let thread = {
+            let captured = Arc::clone(&captured);
+            thread::spawn(move || run_capture(source, captured, vad_tx, started_tx, stop_rx))
+        };
 
-        // Start recording in a separate thread
-        thread::spawn(move || {
-            if let Err(e) = start_recording_internal() {
-                eprintln!("Recording error: {}", e);
-                RECORDING_FLAG.store(false, Ordering::SeqCst);
+        let format = match started_rx.recv() {
+            Ok(Ok(format)) => format,
+            Ok(Err(e)) => {
+                let _ = thread.join();
+                return Err(e);
             }
-        });
-
-        // Wait for the stream to be ready
-        let start = std::time::Instant::now();
-        while !STREAM_READY.load(Ordering::SeqCst) {
-            if start.elapsed() > std::time::Duration::from_secs(5) {
-                RECORDING_FLAG.store(false, Ordering::SeqCst);
-                return Err("Recording failed to start in time".to_string());
+            Err(_) => {
+                let _ = thread.join();
+                return Err("Recording thread exited before the stream started".to_string());
             }
-            thread::sleep(std::time::Duration::from_millis(10));
-        }
+        };
 
-        self.sample_rate = SAMPLE_RATE.load(Ordering::SeqCst);
+        self.captured = captured;
+        self.capture = Some(Capture {
+            stop: stop_tx,
+            thread,
+        });
+        self.sample_rate = format.sample_rate;
+        self.format = Some(format);
+        self.vad_events = Some(vad_rx);
         Ok(())
     }
 
-    pub fn stop_recording(&mut self) -> Result<Vec<f32>, String> {
-        if !RECORDING_FLAG.load(Ordering::SeqCst) {
-            return Err("Not recording".to_string());
-        }
-
-        RECORDING_FLAG.store(false, Ordering::SeqCst);
+    /// Take the error that ended the stream, e.g. the device being unplugged
+    pub fn take_stream_error(&self) -> Option<String> {
+        self.captured
+            .error
+            .lock()
+            .ok()
+            .and_then(|mut error| error.take())
+    }
 
-        // Give the recording thread time to clean up
-        thread::sleep(std::time::Duration::from_millis(100));
+    pub fn stop_recording(&mut self) -> Result<Vec<f32>, String> {
+        let capture = self.capture.take().ok_or("Not recording")?;
+        capture.finish();
 
-        let samples = if let Ok(mut lock) = SAMPLES.lock() {
+        let samples = if let Ok(mut lock) = self.captured.samples.lock() {
             std::mem::take(&mut *lock)
         } else {
             Vec::new()
         };
 
-        println!("Recording stopped: {} samples", samples.len());
+        println!(
+            "Recording stopped: {} samples, {} dropped",
+            samples.len(),
+            self.overruns()
+        );
 
         // Resample to 16kHz if needed (Whisper requires 16kHz)
         let resampled = if self.sample_rate != 16000 {
@@ -83,12 +176,36 @@ impl AudioRecorder {
         Ok(resampled)
     }
 
+    /// Drain voice activity events produced since the last call
+    pub fn poll_vad_events(&self) -> Vec<VadEvent> {
+        self.vad_events
+            .as_ref()
+            .map(|rx| rx.try_iter().collect())
+            .unwrap_or_default()
+    }
+
+    /// Sample rate of the current (or last) recording
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// Samples the current (or last) recording lost because the consumer
+    /// thread fell behind the audio callback
+    pub fn overruns(&self) -> u64 {
+        self.captured.overruns.load(Ordering::SeqCst)
+    }
+
+    /// Device format of the current (or last) recording
+    pub fn capture_format(&self) -> Option<CaptureFormat> {
+        self.format.clone()
+    }
+
     pub fn is_recording(&self) -> bool {
-        RECORDING_FLAG.load(Ordering::SeqCst)
+        self.capture.is_some()
     }
 
     pub fn get_audio_level(&self) -> f32 {
-        if let Ok(lock) = SAMPLES.lock() {
+        if let Ok(lock) = self.captured.samples.lock() {
             if lock.is_empty() {
                 return 0.0;
             }
@@ -108,7 +225,7 @@ impl AudioRecorder {
     /// Get current samples without stopping the recording
     /// Returns samples from the specified position onwards
     pub fn get_samples_from(&self, from_sample: usize) -> Vec<f32> {
-        if let Ok(lock) = SAMPLES.lock() {
+        if let Ok(lock) = self.captured.samples.lock() {
             if from_sample < lock.len() {
                 lock[from_sample..].to_vec()
             } else {
@@ -121,21 +238,12 @@ impl AudioRecorder {
 
     /// Get total number of samples recorded so far
     pub fn get_sample_count(&self) -> usize {
-        if let Ok(lock) = SAMPLES.lock() {
+        if let Ok(lock) = self.captured.samples.lock() {
             lock.len()
         } else {
             0
         }
     }
-
-    /// Get all current samples without stopping
-    pub fn get_current_samples(&self) -> Vec<f32> {
-        if let Ok(lock) = SAMPLES.lock() {
-            lock.clone()
-        } else {
-            Vec::new()
-        }
-    }
 }
 
 impl Default for AudioRecorder {
@@ -144,124 +252,312 @@ impl Default for AudioRecorder {
     }
 }
 
-fn start_recording_internal() -> Result<(), String> {
-    let host = cpal::default_host();
+impl Drop for AudioRecorder {
+    fn drop(&mut self) {
+        if let Some(capture) = self.capture.take() {
+            capture.finish();
+        }
+    }
+}
+
+/// Pick an input device: exact name match, then case-insensitive substring,
+/// then (if allowed) the system default
+fn select_input_device(
+    host: &cpal::Host,
+    preferred: Option<&str>,
+    allow_fallback: bool,
+) -> Result<(cpal::Device, InputDeviceChoice), String> {
+    let mut fallback_reason = None;
+
+    if let Some(wanted) = preferred.filter(|name| !name.trim().is_empty()) {
+        let devices: Vec<cpal::Device> = host
+            .input_devices()
+            .map_err(|e| format!("Failed to list input devices: {}", e))?
+            .collect();
+        let names: Vec<String> = devices
+            .iter()
+            .map(|d| d.name().unwrap_or_default())
+            .collect();
+
+        let wanted_lower = wanted.to_lowercase();
+        let found = names.iter().position(|name| name == wanted).or_else(|| {
+            names
+                .iter()
+                .position(|name| name.to_lowercase().contains(&wanted_lower))
+        });
+
+        if let Some(index) = found {
+            let name = names[index].clone();
+            let device = devices
+                .into_iter()
+                .nth(index)
+                .expect("index from same list");
+            return Ok((
+                device,
+                InputDeviceChoice {
+                    name,
+                    fallback_reason: None,
+                },
+            ));
+        }
+
+        let reason = if names.is_empty() {
+            format!(
+                "Input device '{}' not found (no input devices available)",
+                wanted
+            )
+        } else {
+            format!(
+                "Input device '{}' not found (available: {})",
+                wanted,
+                names.join(", ")
+            )
+        };
+        if !allow_fallback {
+            return Err(reason);
+        }
+        fallback_reason = Some(reason);
+    }
+
     let device = host
         .default_input_device()
         .ok_or("No input device available")?;
+    let name = device.name().unwrap_or_else(|_| "default".to_string());
+
+    Ok((
+        device,
+        InputDeviceChoice {
+            name,
+            fallback_reason,
+        },
+    ))
+}
 
-    println!("Using audio device: {:?}", device.name());
+/// The guard keeping a stream running, the ring its callback fills with mono
+/// samples, and what the device delivers
+type OpenedSource = (Box<dyn Any>, HeapCons<f32>, CaptureFormat);
 
-    // Get supported config
-    let supported_configs = device
-        .supported_input_configs()
-        .map_err(|e| format!("Failed to get supported configs: {}", e))?;
+/// Where a recording's audio comes from. It is opened on the capture thread,
+/// which drops the guard to stop the stream.
+trait InputSource: Send {
+    fn open(self: Box<Self>, captured: &Arc<Captured>) -> Result<OpenedSource, String>;
+}
 
-    // Try to find a config that supports 16kHz
-    let target_sample_rate = SampleRate(16000);
-    let mut config: Option<StreamConfig> = None;
+struct CpalSource(cpal::Device);
 
-    for supported_config in supported_configs {
-        if supported_config.min_sample_rate() <= target_sample_rate
-            && supported_config.max_sample_rate() >= target_sample_rate
-        {
-            config = Some(StreamConfig {
-                channels: 1,
-                sample_rate: target_sample_rate,
-                buffer_size: cpal::BufferSize::Default,
-            });
-            break;
-        }
+impl InputSource for CpalSource {
+    fn open(self: Box<Self>, captured: &Arc<Captured>) -> Result<OpenedSource, String> {
+        let (stream, consumer, format) = open_stream(self.0, captured)?;
+        Ok((Box::new(stream), consumer, format))
     }
+}
 
-    // If no exact match, use default
-    let config = config.unwrap_or_else(|| {
-        let default = device.default_input_config().unwrap();
-        StreamConfig {
-            channels: default.channels(),
-            sample_rate: default.sample_rate(),
-            buffer_size: cpal::BufferSize::Default,
+/// Body of a recording's thread: open the stream, report how that went on
+/// `started`, then move audio from the ring to `captured` until `stop`
+fn run_capture(
+    source: Box<dyn InputSource>,
+    captured: Arc<Captured>,
+    vad_tx: Sender<VadEvent>,
+    started: Sender<Result<CaptureFormat, String>>,
+    stop: Receiver<()>,
+) {
+    let (stream, mut consumer, format) = match source.open(&captured) {
+        Ok(opened) => opened,
+        Err(e) => {
+            eprintln!("Recording error: {}", e);
+            let _ = started.send(Err(e));
+            return;
         }
-    });
+    };
+    let mut vad = VoiceActivityDetector::new(format.sample_rate, VadConfig::default());
+    let _ = started.send(Ok(format));
+
+    let mut chunk = vec![0.0f32; CHUNK_SAMPLES];
+    // A dropped sender (the recorder went away) stops us as well
+    while let Err(RecvTimeoutError::Timeout) = stop.recv_timeout(DRAIN_INTERVAL) {
+        drain_ring(&mut consumer, &mut chunk, &captured, &mut vad, &vad_tx);
+    }
+
+    // Stop the callbacks, then collect what they left in the ring
+    drop(stream);
+    drain_ring(&mut consumer, &mut chunk, &captured, &mut vad, &vad_tx);
+    println!("Recording stream closed");
+}
+
+/// Open and start a stream on `device`, returning it with the consuming end
+/// of the ring its callback fills
+fn open_stream(
+    device: cpal::Device,
+    captured: &Arc<Captured>,
+) -> Result<(cpal::Stream, HeapCons<f32>, CaptureFormat), String> {
+    let device_name = device.name().unwrap_or_else(|_| "input device".to_string());
+    println!("Using audio device: {:?}", device_name);
+
+    let supported = choose_config(&device)
+        .map_err(|e| format!("No usable input config for '{}': {}", device_name, e))?;
+    let sample_format = supported.sample_format();
+    let config = supported.config();
 
     let sample_rate = config.sample_rate.0;
     let channels = config.channels;
 
-    SAMPLE_RATE.store(sample_rate, Ordering::SeqCst);
-
-    let samples_ref = Arc::clone(&SAMPLES);
-
-    let err_fn = |err| eprintln!("Audio stream error: {}", err);
-
-    let stream = device
-        .build_input_stream(
-            &config,
-            move |data: &[f32], _: &cpal::InputCallbackInfo| {
-                if RECORDING_FLAG.load(Ordering::SeqCst) {
-                    if let Ok(mut samples) = samples_ref.lock() {
-                        // Convert to mono if stereo
-                        if channels > 1 {
-                            for chunk in data.chunks(channels as usize) {
-                                let mono: f32 = chunk.iter().sum::<f32>() / channels as f32;
-                                samples.push(mono);
-                            }
-                        } else {
-                            samples.extend_from_slice(data);
-                        }
-                    }
-                }
-            },
-            err_fn,
-            None,
-        )
-        .map_err(|e| format!("Failed to build input stream: {}", e))?;
+    // The callback only copies into this ring; the capture thread moves
+    // samples on to `captured` and the VAD. That way the audio thread
+    // never waits for a lock held by a reader of the samples.
+    let (mut producer, consumer) = HeapRb::<f32>::new(sample_rate as usize * RING_SECONDS).split();
+
+    let mut mono = Vec::new();
+    let callback_captured = Arc::clone(captured);
+    let on_samples = move |data: &[f32]| {
+        // Convert to mono if stereo
+        let data = if channels > 1 {
+            mono.clear();
+            mono.extend(
+                data.chunks(channels as usize)
+                    .map(|chunk| chunk.iter().sum::<f32>() / channels as f32),
+            );
+            &mono[..]
+        } else {
+            data
+        };
+
+        push_captured(&mut producer, data, &callback_captured.overruns);
+    };
+
+    let err_device_name = device_name.clone();
+    let err_captured = Arc::clone(captured);
+    let err_fn = move |err: cpal::StreamError| {
+        eprintln!("Audio stream error: {}", err);
+        if let cpal::StreamError::DeviceNotAvailable = err {
+            if let Ok(mut error) = err_captured.error.lock() {
+                *error = Some(format!(
+                    "Input device '{}' disconnected during recording",
+                    err_device_name
+                ));
+            }
+        }
+    };
+
+    let stream = match sample_format {
+        SampleFormat::F32 => build_stream::<f32>(&device, &config, on_samples, err_fn),
+        SampleFormat::F64 => build_stream::<f64>(&device, &config, on_samples, err_fn),
+        SampleFormat::I8 => build_stream::<i8>(&device, &config, on_samples, err_fn),
+        SampleFormat::I16 => build_stream::<i16>(&device, &config, on_samples, err_fn),
+        SampleFormat::I32 => build_stream::<i32>(&device, &config, on_samples, err_fn),
+        SampleFormat::I64 => build_stream::<i64>(&device, &config, on_samples, err_fn),
+        SampleFormat::U8 => build_stream::<u8>(&device, &config, on_samples, err_fn),
+        SampleFormat::U16 => build_stream::<u16>(&device, &config, on_samples, err_fn),
+        SampleFormat::U32 => build_stream::<u32>(&device, &config, on_samples, err_fn),
+        SampleFormat::U64 => build_stream::<u64>(&device, &config, on_samples, err_fn),
+        other => {
+            return Err(format!(
+                "Unsupported sample format {} on '{}'",
+                other, device_name
+            ))
+        }
+    }
+    .map_err(|e| format!("Failed to build {} input stream: {}", sample_format, e))?;
 
     stream
         .play()
         .map_err(|e| format!("Failed to play stream: {}", e))?;
 
     println!(
-        "Recording started: {}Hz, {} channels",
-        sample_rate, channels
+        "Recording started: {}Hz, {} channels, {}",
+        sample_rate, channels, sample_format
     );
 
-    // Signal that we're ready
-    STREAM_READY.store(true, Ordering::SeqCst);
+    let format = CaptureFormat {
+        device: device_name,
+        sample_format: sample_format.to_string(),
+        sample_rate,
+        channels,
+    };
+    Ok((stream, consumer, format))
+}
 
-    // Keep the stream alive while recording
-    while RECORDING_FLAG.load(Ordering::SeqCst) {
-        thread::sleep(std::time::Duration::from_millis(10));
+/// Audio thread side of the ring: copy `data` in without waiting, counting
+/// what doesn't fit
+fn push_captured(producer: &mut HeapProd<f32>, data: &[f32], overruns: &AtomicU64) {
+    let pushed = producer.push_slice(data);
+    if pushed < data.len() {
+        overruns.fetch_add((data.len() - pushed) as u64, Ordering::Relaxed);
     }
-
-    // Stream is dropped here when recording stops
-    drop(stream);
-    println!("Recording stream closed");
-
-    Ok(())
 }
 
-// Simple linear resampling
-fn resample(samples: &[f32], source_rate: u32, target_rate: u32) -> Vec<f32> {
-    if source_rate == target_rate || samples.is_empty() {
-        return samples.to_vec();
-    }
+/// Move everything in the ring to the recording's samples and run it
+/// through the VAD
+fn drain_ring(
+    consumer: &mut HeapCons<f32>,
+    chunk: &mut [f32],
+    captured: &Captured,
+    vad: &mut VoiceActivityDetector,
+    vad_tx: &Sender<VadEvent>,
+) {
+    loop {
+        let count = consumer.pop_slice(chunk);
+        if count == 0 {
+            return;
+        }
 
-    let ratio = source_rate as f64 / target_rate as f64;
-    let new_len = (samples.len() as f64 / ratio) as usize;
-    let mut result = Vec::with_capacity(new_len);
+        if let Ok(mut samples) = captured.samples.lock() {
+            samples.extend_from_slice(&chunk[..count]);
+        }
+        vad.process(&chunk[..count], |event| {
+            let _ = vad_tx.send(event);
+        });
+    }
+}
 
-    for i in 0..new_len {
-        let src_idx = i as f64 * ratio;
-        let src_idx_floor = src_idx.floor() as usize;
-        let src_idx_ceil = (src_idx_floor + 1).min(samples.len() - 1);
-        let frac = src_idx - src_idx_floor as f64;
+/// Pick a stream config. 16kHz saves resampling, so take a range that
+/// offers it (fewest channels, then f32, preferred); otherwise the
+/// device's default. Either way the device's own sample format is kept.
+fn choose_config(device: &cpal::Device) -> Result<SupportedStreamConfig, String> {
+    let target_sample_rate = SampleRate(16000);
 
-        let sample =
-            samples[src_idx_floor] * (1.0 - frac as f32) + samples[src_idx_ceil] * frac as f32;
-        result.push(sample);
+    let ranges = device
+        .supported_input_configs()
+        .map_err(|e| format!("Failed to get supported configs: {}", e))?;
+    let at_16k = ranges
+        .filter(|range| {
+            range.min_sample_rate() <= target_sample_rate
+                && range.max_sample_rate() >= target_sample_rate
+        })
+        .min_by_key(|range| (range.channels(), range.sample_format() != SampleFormat::F32));
+
+    match at_16k {
+        Some(range) => Ok(range.with_sample_rate(target_sample_rate)),
+        None => device
+            .default_input_config()
+            .map_err(|e| format!("Failed to get default config: {}", e)),
     }
+}
 
-    result
+/// Open an input stream in the device's sample format `T` and hand
+/// `on_samples` the data converted to f32
+fn build_stream<T>(
+    device: &cpal::Device,
+    config: &StreamConfig,
+    mut on_samples: impl FnMut(&[f32]) + Send + 'static,
+    err_fn: impl FnMut(cpal::StreamError) + Send + 'static,
+) -> Result<cpal::Stream, cpal::BuildStreamError>
+where
+    T: SizedSample,
+    f32: FromSample<T>,
+{
+    // Reused between callbacks so the audio thread doesn't allocate
+    let mut converted = Vec::new();
+
+    device.build_input_stream(
+        config,
+        move |data: &[T], _: &cpal::InputCallbackInfo| {
+            converted.clear();
+            converted.extend(data.iter().map(|sample| sample.to_sample::<f32>()));
+            on_samples(&converted);
+        },
+        err_fn,
+        None,
+    )
 }
 
 pub fn get_input_devices() -> Vec<String> {
@@ -270,3 +566,249 @@ pub fn get_input_devices() -> Vec<String> {
         .map(|devices| devices.filter_map(|d| d.name().ok()).collect())
         .unwrap_or_default()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The ring plus what the capture thread needs to drain it
+    struct Pipe {
+        producer: HeapProd<f32>,
+        consumer: HeapCons<f32>,
+        chunk: Vec<f32>,
+        captured: Captured,
+        vad: VoiceActivityDetector,
+        vad_tx: Sender<VadEvent>,
+    }
+
+    impl Pipe {
+        fn new(capacity: usize) -> Self {
+            let (producer, consumer) = HeapRb::<f32>::new(capacity).split();
+            let (vad_tx, _) = mpsc::channel();
+            Self {
+                producer,
+                consumer,
+                chunk: vec![0.0; CHUNK_SAMPLES],
+                captured: Captured::default(),
+                vad: VoiceActivityDetector::new(16000, VadConfig::default()),
+                vad_tx,
+            }
+        }
+
+        fn push(&mut self, data: &[f32]) {
+            push_captured(&mut self.producer, data, &self.captured.overruns);
+        }
+
+        fn drain(&mut self) {
+            drain_ring(
+                &mut self.consumer,
+                &mut self.chunk,
+                &self.captured,
+                &mut self.vad,
+                &self.vad_tx,
+            );
+        }
+
+        fn samples(&self) -> Vec<f32> {
+            self.captured.samples.lock().unwrap().clone()
+        }
+
+        fn overruns(&self) -> u64 {
+            self.captured.overruns.load(Ordering::SeqCst)
+        }
+    }
+
+    /// `count` samples numbered from `start`; exact in f32 below 2^24
+    fn numbered(start: usize, count: usize) -> Vec<f32> {
+        (start..start + count).map(|n| n as f32).collect()
+    }
+
+    #[test]
+    fn overruns_count_exactly_what_did_not_fit() {
+        let mut pipe = Pipe::new(1000);
+
+        pipe.push(&numbered(0, 600));
+        assert_eq!(pipe.overruns(), 0);
+        pipe.push(&numbered(600, 600));
+        assert_eq!(pipe.overruns(), 200);
+        pipe.push(&numbered(1200, 50));
+        assert_eq!(pipe.overruns(), 250);
+
+        // What fit is kept in order; the rest is gone
+        pipe.drain();
+        assert_eq!(pipe.samples(), numbered(0, 1000));
+
+        pipe.push(&numbered(1250, 300));
+        pipe.drain();
+        assert_eq!(pipe.samples()[1000..], numbered(1250, 300)[..]);
+        assert_eq!(pipe.overruns(), 250);
+    }
+
+    #[test]
+    fn drain_empties_more_than_one_chunk() {
+        let mut pipe = Pipe::new(CHUNK_SAMPLES * 3);
+        pipe.push(&numbered(0, CHUNK_SAMPLES * 2 + 7));
+        pipe.drain();
+        assert_eq!(pipe.samples(), numbered(0, CHUNK_SAMPLES * 2 + 7));
+        pipe.drain();
+        assert_eq!(pipe.samples().len(), CHUNK_SAMPLES * 2 + 7);
+    }
+
+    /// Push `total` numbered samples from another thread in 480-sample
+    /// blocks, `blocks_per_drain` at a time, and drain after each batch. The
+    /// threads take turns over channels, so what fits in the ring does not
+    /// depend on scheduling. Returns the samples that arrived and the overrun
+    /// count.
+    fn run_in_lockstep(ring: usize, total: usize, blocks_per_drain: usize) -> (Vec<f32>, u64) {
+        let (mut producer, mut consumer) = HeapRb::<f32>::new(ring).split();
+        let captured = Arc::new(Captured::default());
+        let (vad_tx, _vad_rx) = mpsc::channel();
+        let mut vad = VoiceActivityDetector::new(16000, VadConfig::default());
+        let mut chunk = vec![0.0; CHUNK_SAMPLES];
+        let (pushed_tx, pushed_rx) = mpsc::channel::<()>();
+        let (drained_tx, drained_rx) = mpsc::channel::<()>();
+
+        let audio_captured = Arc::clone(&captured);
+        let audio_thread = thread::spawn(move || {
+            let mut next = 0;
+            while next < total {
+                for _ in 0..blocks_per_drain {
+                    let block = 480.min(total - next);
+                    push_captured(
+                        &mut producer,
+                        &numbered(next, block),
+                        &audio_captured.overruns,
+                    );
+                    next += block;
+                }
+                pushed_tx.send(()).unwrap();
+                drained_rx.recv().unwrap();
+            }
+        });
+
+        // Ends when the audio thread hangs up after its last batch
+        while pushed_rx.recv().is_ok() {
+            drain_ring(&mut consumer, &mut chunk, &captured, &mut vad, &vad_tx);
+            drained_tx.send(()).ok();
+        }
+        audio_thread.join().unwrap();
+
+        let samples = captured.samples.lock().unwrap().clone();
+        (samples, captured.overruns.load(Ordering::SeqCst))
+    }
+
+    #[test]
+    fn nothing_is_lost_or_reordered_when_the_consumer_keeps_up() {
+        let total = 200 * 480;
+        let (samples, overruns) = run_in_lockstep(16000 * RING_SECONDS, total, 1);
+        assert_eq!(overruns, 0);
+        assert_eq!(samples, numbered(0, total));
+    }
+
+    #[test]
+    fn a_slow_consumer_loses_only_what_overruns_counts() {
+        // Five blocks arrive between drains, 2.5 times what the ring holds
+        let total = 100 * 480;
+        let (samples, overruns) = run_in_lockstep(960, total, 5);
+
+        assert_eq!(overruns, 20 * (5 * 480 - 960));
+        assert_eq!(samples.len() as u64 + overruns, total as u64);
+        // Each batch keeps its first 960 samples, in order
+        let expected: Vec<f32> = (0..20)
+            .flat_map(|batch| numbered(batch * 5 * 480, 960))
+            .collect();
+        assert_eq!(samples, expected);
+    }
+
+    /// Hands over a fixed recording as soon as it is opened
+    struct ScriptedSource {
+        samples: Vec<f32>,
+        device: &'static str,
+    }
+
+    impl InputSource for ScriptedSource {
+        fn open(self: Box<Self>, captured: &Arc<Captured>) -> Result<OpenedSource, String> {
+            let (mut producer, consumer) = HeapRb::<f32>::new(self.samples.len().max(1)).split();
+            push_captured(&mut producer, &self.samples, &captured.overruns);
+            let format = CaptureFormat {
+                device: self.device.to_string(),
+                sample_format: "f32".to_string(),
+                sample_rate: 16000,
+                channels: 1,
+            };
+            Ok((Box::new(()), consumer, format))
+        }
+    }
+
+    struct FailingSource;
+
+    impl InputSource for FailingSource {
+        fn open(self: Box<Self>, _: &Arc<Captured>) -> Result<OpenedSource, String> {
+            Err("Failed to play stream: device busy".to_string())
+        }
+    }
+
+    fn scripted(device: &'static str, samples: Vec<f32>) -> Box<dyn InputSource> {
+        Box::new(ScriptedSource { samples, device })
+    }
+
+    #[test]
+    fn two_recorders_keep_separate_buffers() {
+        let mut first = AudioRecorder::new();
+        let mut second = AudioRecorder::new();
+        first
+            .start_with(scripted("first", numbered(0, 3000)))
+            .unwrap();
+        second
+            .start_with(scripted("second", numbered(5000, 700)))
+            .unwrap();
+        assert!(first.is_recording() && second.is_recording());
+
+        assert_eq!(first.capture_format().unwrap().device, "first");
+        assert_eq!(second.capture_format().unwrap().device, "second");
+
+        assert_eq!(second.stop_recording().unwrap(), numbered(5000, 700));
+        assert!(first.is_recording());
+        assert_eq!(first.stop_recording().unwrap(), numbered(0, 3000));
+    }
+
+    #[test]
+    fn a_failed_start_reports_the_sources_error() {
+        let mut recorder = AudioRecorder::new();
+        assert_eq!(
+            recorder.start_with(Box::new(FailingSource)),
+            Err("Failed to play stream: device busy".to_string())
+        );
+        assert!(!recorder.is_recording());
+
+        // The recorder is still usable afterwards
+        recorder
+            .start_with(scripted("retry", numbered(0, 10)))
+            .unwrap();
+        assert_eq!(recorder.stop_recording().unwrap(), numbered(0, 10));
+    }
+
+    #[test]
+    fn stopping_without_a_start_is_an_error() {
+        let mut recorder = AudioRecorder::new();
+        assert_eq!(recorder.stop_recording(), Err("Not recording".to_string()));
+
+        recorder.start_with(scripted("once", Vec::new())).unwrap();
+        assert_eq!(recorder.stop_recording(), Ok(Vec::new()));
+        assert_eq!(recorder.stop_recording(), Err("Not recording".to_string()));
+    }
+
+    #[test]
+    fn starting_twice_is_an_error() {
+        let mut recorder = AudioRecorder::new();
+        recorder
+            .start_with(scripted("first", numbered(0, 10)))
+            .unwrap();
+        assert_eq!(
+            recorder.start_with(scripted("second", numbered(100, 10))),
+            Err("Already recording".to_string())
+        );
+        assert_eq!(recorder.capture_format().unwrap().device, "first");
+        assert_eq!(recorder.stop_recording().unwrap(), numbered(0, 10));
+    }
+}
@@ -0,0 +1,267 @@
+use std::fs::File;
+use std::path::Path;
+
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+use crate::resample::resample;
+use crate::whisper::WHISPER_SAMPLE_RATE;
+
+/// Mono audio decoded from a file, at the file's own sample rate
+#[derive(Debug)]
+pub struct DecodedAudio {
+    pub samples: Vec<f32>,
+    pub sample_rate: u32,
+    pub channels: usize,
+}
+
+impl DecodedAudio {
+    pub fn duration_ms(&self) -> u64 {
+        if self.sample_rate == 0 {
+            return 0;
+        }
+        self.samples.len() as u64 * 1000 / self.sample_rate as u64
+    }
+
+    /// Samples resampled to what Whisper expects
+    pub fn to_whisper(&self) -> Vec<f32> {
+        resample(&self.samples, self.sample_rate, WHISPER_SAMPLE_RATE)
+    }
+}
+
+/// Decode a WAV, FLAC or Ogg Vorbis file and downmix it to mono
+pub fn decode_file(path: &Path) -> Result<DecodedAudio, String> {
+    let is_wav = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("wav"));
+
+    // hound handles the common WAV layouts; symphonia covers the rest
+    // (and WAV variants hound rejects, like WAVE_FORMAT_EXTENSIBLE floats)
+    if is_wav {
+        match decode_wav(path) {
+            Ok(audio) => return Ok(audio),
+            Err(e) => eprintln!("hound could not read {:?} ({}), trying symphonia", path, e),
+        }
+    }
+
+    decode_with_symphonia(path)
+}
+
+fn decode_wav(path: &Path) -> Result<DecodedAudio, String> {
+    let mut reader =
+        hound::WavReader::open(path).map_err(|e| format!("Failed to open {:?}: {}", path, e))?;
+    let spec = reader.spec();
+
+    let interleaved: Vec<f32> = match spec.sample_format {
+        hound::SampleFormat::Float => reader
+            .samples::<f32>()
+            .collect::<Result<_, _>>()
+            .map_err(|e| format!("Failed to read {:?}: {}", path, e))?,
+        hound::SampleFormat::Int => {
+            let scale = (1u64 << (spec.bits_per_sample.max(1) - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|s| s.map(|s| s as f32 / scale))
+                .collect::<Result<_, _>>()
+                .map_err(|e| format!("Failed to read {:?}: {}", path, e))?
+        }
+    };
+
+    let channels = spec.channels.max(1) as usize;
+    Ok(DecodedAudio {
+        samples: downmix(&interleaved, channels),
+        sample_rate: spec.sample_rate,
+        channels,
+    })
+}
+
+fn decode_with_symphonia(path: &Path) -> Result<DecodedAudio, String> {
+    let file = File::open(path).map_err(|e| format!("Failed to open {:?}: {}", path, e))?;
+    let stream = MediaSourceStream::new(Box::new(file), Default::default());
+
+    let mut hint = Hint::new();
+    if let Some(extension) = path.extension().and_then(|e| e.to_str()) {
+        hint.with_extension(extension);
+    }
+
+    let probed = symphonia::default::get_probe()
+        .format(
+            &hint,
+            stream,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .map_err(|e| format!("Unsupported audio file {:?}: {}", path, e))?;
+    let mut format = probed.format;
+
+    let track = format
+        .tracks()
+        .iter()
+        .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or_else(|| format!("No audio track in {:?}", path))?;
+    let track_id = track.id;
+    let mut sample_rate = track.codec_params.sample_rate.unwrap_or(0);
+
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions::default())
+        .map_err(|e| format!("Unsupported codec in {:?}: {}", path, e))?;
+
+    let mut samples = Vec::new();
+    let mut channels = 1;
+
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(SymphoniaError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                break
+            }
+            Err(e) => return Err(format!("Failed to read {:?}: {}", path, e)),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+
+        match decoder.decode(&packet) {
+            Ok(decoded) => {
+                let spec = *decoded.spec();
+                sample_rate = spec.rate;
+                channels = spec.channels.count().max(1);
+
+                let mut buffer = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+                buffer.copy_interleaved_ref(decoded);
+                samples.extend(downmix(buffer.samples(), channels));
+            }
+            // A corrupt packet loses a few milliseconds, not the whole file
+            Err(SymphoniaError::DecodeError(e)) => {
+                eprintln!("Skipping undecodable packet in {:?}: {}", path, e);
+            }
+            Err(e) => return Err(format!("Failed to decode {:?}: {}", path, e)),
+        }
+    }
+
+    if sample_rate == 0 {
+        return Err(format!("Unknown sample rate in {:?}", path));
+    }
+
+    Ok(DecodedAudio {
+        samples,
+        sample_rate,
+        channels,
+    })
+}
+
+fn downmix(interleaved: &[f32], channels: usize) -> Vec<f32> {
+    if channels <= 1 {
+        return interleaved.to_vec();
+    }
+    interleaved
+        .chunks(channels)
+        .map(|frame| frame.iter().sum::<f32>() / frame.len() as f32)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_wav<S: hound::Sample + Copy>(path: &Path, spec: hound::WavSpec, samples: &[S]) {
+        let mut writer = hound::WavWriter::create(path, spec).unwrap();
+        for &sample in samples {
+            writer.write_sample(sample).unwrap();
+        }
+        writer.finalize().unwrap();
+    }
+
+    fn spec(channels: u16, bits: u16, format: hound::SampleFormat) -> hound::WavSpec {
+        hound::WavSpec {
+            channels,
+            sample_rate: 16000,
+            bits_per_sample: bits,
+            sample_format: format,
+        }
+    }
+
+    #[test]
+    fn downmix_averages_each_frame() {
+        assert_eq!(downmix(&[0.5, -0.5, 1.0], 1), [0.5, -0.5, 1.0]);
+        assert_eq!(downmix(&[1.0, 0.0, 0.5, 0.5], 2), [0.5, 0.5]);
+        assert_eq!(downmix(&[0.25, 0.5, 0.75], 3), [0.5]);
+        // A trailing partial frame averages what is there
+        assert_eq!(downmix(&[1.0, 0.0, 0.5], 2), [0.5, 0.5]);
+        assert_eq!(downmix(&[], 2), Vec::<f32>::new());
+    }
+
+    #[test]
+    fn decodes_16_bit_wav() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("speech.wav");
+        write_wav(
+            &path,
+            spec(1, 16, hound::SampleFormat::Int),
+            &[0i16, 16384, -32768],
+        );
+
+        let audio = decode_file(&path).unwrap();
+        assert_eq!(audio.samples, [0.0, 0.5, -1.0]);
+        assert_eq!(audio.sample_rate, 16000);
+        assert_eq!(audio.channels, 1);
+    }
+
+    #[test]
+    fn decodes_float_stereo_wav_to_mono() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("stereo.WAV");
+        write_wav(
+            &path,
+            spec(2, 32, hound::SampleFormat::Float),
+            &[1.0f32, 0.0, 0.25, 0.75, -0.5, -0.5],
+        );
+
+        let audio = decode_file(&path).unwrap();
+        assert_eq!(audio.samples, [0.5, 0.5, -0.5]);
+        assert_eq!(audio.channels, 2);
+        assert_eq!(audio.duration_ms(), 0);
+    }
+
+    #[test]
+    fn symphonia_reads_wav_too() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("speech.wav");
+        let samples: Vec<i16> = (0..1600).map(|i| (i * 16) as i16).collect();
+        write_wav(&path, spec(2, 16, hound::SampleFormat::Int), &samples);
+
+        let audio = decode_with_symphonia(&path).unwrap();
+        assert_eq!(audio.sample_rate, 16000);
+        assert_eq!(audio.channels, 2);
+        assert_eq!(audio.samples.len(), 800);
+        assert_eq!(audio.duration_ms(), 50);
+        assert!((audio.samples[1] - 40.0 / 32768.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn unreadable_files_are_errors() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let missing = dir.path().join("missing.flac");
+        assert!(decode_file(&missing)
+            .unwrap_err()
+            .starts_with("Failed to open"));
+
+        let text = dir.path().join("notes.mp4");
+        std::fs::write(&text, "not audio at all").unwrap();
+        assert!(decode_file(&text)
+            .unwrap_err()
+            .starts_with("Unsupported audio file"));
+
+        // A broken WAV falls through to symphonia, which rejects it too
+        let broken = dir.path().join("broken.wav");
+        std::fs::write(&broken, b"RIFF\x04\x00\x00\x00WAVE").unwrap();
+        assert!(decode_file(&broken).is_err());
+    }
+}
@@ -0,0 +1,575 @@
+use clap::{Parser, Subcommand};
+use std::path::{Path, PathBuf};
+
+use crate::audio_file;
+use crate::clipboard;
+use crate::config;
+use crate::control::{self, Request};
+use crate::history::{self, Entry};
+use crate::inject::InjectorChain;
+use crate::profiles::{Replacements, TextRules};
+use crate::rules::Rules;
+use crate::settings::{self, Settings};
+use crate::transcript::OutputFormat;
+use crate::undo;
+use crate::voice_commands::VoiceCommands;
+use crate::whisper::{self, WhisperEngine};
+
+/// HyprWhisper - Local Speech-to-Text for Hyprland.
+///
+/// Run without a subcommand to start dictating, or to stop the running
+/// dictation if one is already active.
+#[derive(Parser)]
+#[command(name = "hyprwhisper", version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Stop the running dictation, or start one if none is running
+    Toggle,
+    /// Start dictating (launches HyprWhisper if it isn't running)
+    Start,
+    /// Finish the running dictation and type the rest of the text
+    Stop,
+    /// Abort the running dictation without typing anything
+    Cancel,
+    /// Show what the running instance is doing
+    Status {
+        /// Print the raw JSON reply (for Waybar and scripts)
+        #[arg(long)]
+        json: bool,
+    },
+    /// List, download or delete Whisper models
+    Models {
+        #[command(subcommand)]
+        command: ModelsCommand,
+    },
+    /// Transcribe a WAV, FLAC or Ogg Vorbis file and print the text
+    Transcribe {
+        file: PathBuf,
+        /// Model filename or name, defaults to the configured model
+        #[arg(long)]
+        model: Option<String>,
+        /// Language code or "auto", defaults to the configured language
+        #[arg(long)]
+        language: Option<String>,
+        /// Output format, defaults to the --output extension or plain text
+        #[arg(long, value_enum)]
+        format: Option<OutputFormat>,
+        /// Write to this file instead of stdout
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Manage the terms Whisper is primed with
+    Vocabulary {
+        #[command(subcommand)]
+        command: VocabularyCommand,
+    },
+    /// Work with the text rules from the config
+    Rules {
+        #[command(subcommand)]
+        command: RulesCommand,
+    },
+    /// Backspace over the last dictation, if its window still has focus
+    UndoLast,
+    /// Look up, copy or re-type past dictations
+    History {
+        #[command(subcommand)]
+        command: HistoryCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum ModelsCommand {
+    /// List available models and which are downloaded
+    List,
+    /// Download a model, e.g. `base.en` or `ggml-small.bin`
+    Download { name: String },
+    /// Delete a downloaded model
+    Delete { name: String },
+}
+
+#[derive(Subcommand)]
+enum HistoryCommand {
+    /// Show the most recent dictations (`!` marks text that never arrived)
+    List {
+        /// How many to show
+        #[arg(long, short = 'n', default_value_t = 20)]
+        limit: usize,
+        /// Print entries as JSON lines
+        #[arg(long)]
+        json: bool,
+    },
+    /// Show dictations whose text or window class contains QUERY
+    Search {
+        query: String,
+        #[arg(long, short = 'n', default_value_t = 20)]
+        limit: usize,
+        #[arg(long)]
+        json: bool,
+    },
+    /// Copy a dictation to the clipboard, the latest if no ID is given
+    Copy { id: Option<u64> },
+    /// Type a dictation into the focused window, e.g. from a key binding
+    Type { id: Option<u64> },
+    /// Write a dictation out with timings, the latest if no ID is given
+    Export {
+        id: Option<u64>,
+        /// Output format, defaults to the --output extension or plain text
+        #[arg(long, value_enum)]
+        format: Option<OutputFormat>,
+        /// Write to this file instead of stdout
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Delete the whole history
+    Clear,
+}
+
+#[derive(Subcommand)]
+enum RulesCommand {
+    /// Show what a transcript would be typed as, step by step
+    DryRun {
+        /// Transcript to process, read from stdin if omitted
+        text: Option<String>,
+        /// Also apply this profile, as if dictating into its application
+        #[arg(long)]
+        profile: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum VocabularyCommand {
+    /// Show the vocabulary and corrections
+    List,
+    /// Add a term, e.g. `add kubectl --heard "cube control"`
+    Add {
+        term: String,
+        /// What Whisper wrote instead; it will be replaced with the term
+        #[arg(long)]
+        heard: Option<String>,
+    },
+}
+
+/// Parse the command line and run headless subcommands.
+/// Returns `Some(exit_code)` when handled, `None` when the GUI should start.
+pub fn dispatch() -> Option<i32> {
+    let cli = Cli::parse();
+
+    let result = match cli.command? {
+        // Without a running instance these launch the GUI, which starts dictating
+        Command::Toggle => return send_or_launch(Request::Toggle),
+        Command::Start => return send_or_launch(Request::Start),
+        Command::Stop => send(Request::Stop),
+        Command::Cancel => send(Request::Cancel),
+        Command::Status { json } => status(json),
+        Command::Models { command } => models(command),
+        Command::Transcribe {
+            file,
+            model,
+            language,
+            format,
+            output,
+        } => transcribe(&file, model, language, format, output),
+        Command::Vocabulary { command } => vocabulary(command),
+        Command::Rules {
+            command: RulesCommand::DryRun { text, profile },
+        } => dry_run(text, profile),
+        Command::History { command } => history(command),
+        Command::UndoLast => undo_last(),
+    };
+
+    Some(match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("hyprwhisper: {}", e);
+            1
+        }
+    })
+}
+
+fn send(request: Request) -> Result<(), String> {
+    let response = control::send(&request)?;
+    match response.error {
+        Some(error) => Err(error),
+        None => Ok(()),
+    }
+}
+
+fn send_or_launch(request: Request) -> Option<i32> {
+    if control::get_socket_path().exists() {
+        match send(request) {
+            Ok(()) => return Some(0),
+            Err(e) if control::send(&Request::Status).is_ok() => {
+                eprintln!("hyprwhisper: {}", e);
+                return Some(1);
+            }
+            // Stale socket from a crashed instance
+            Err(_) => {}
+        }
+    }
+    None
+}
+
+/// Settings as the app would load them; problems are reported, not fatal
+fn load_settings() -> Settings {
+    let (settings, errors) = config::load_layered();
+    for error in errors {
+        eprintln!("hyprwhisper: {}", error);
+    }
+    settings
+}
+
+/// Undo through the running instance if there is one, so it can refuse
+/// while dictating; otherwise undo from here
+fn undo_last() -> Result<(), String> {
+    if control::get_socket_path().exists() {
+        if let Ok(response) = control::send(&Request::UndoLast) {
+            return match response.error {
+                Some(error) => Err(error),
+                None => Ok(()),
+            };
+        }
+    }
+
+    let settings = load_settings();
+    let count = undo::undo_last(&settings)?;
+    eprintln!("Deleted {} characters", count);
+    Ok(())
+}
+
+fn status(json: bool) -> Result<(), String> {
+    let response = control::send(&Request::Status)?;
+
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string(&response).map_err(|e| e.to_string())?
+        );
+        return Ok(());
+    }
+
+    let status = response.status.ok_or_else(|| {
+        response
+            .error
+            .unwrap_or_else(|| "No status in reply".to_string())
+    })?;
+
+    println!(
+        "{}",
+        if status.recording {
+            "recording"
+        } else {
+            "idle"
+        }
+    );
+    println!(
+        "model:    {}{}",
+        status.model,
+        if status.model_loaded {
+            ""
+        } else {
+            " (not loaded)"
+        }
+    );
+    println!("language: {}", status.language);
+    if let Some(input) = &status.input {
+        println!(
+            "input:    {} ({}, {} Hz, {} channel(s))",
+            input.device, input.sample_format, input.sample_rate, input.channels
+        );
+    }
+    if status.overruns > 0 {
+        println!("dropped:  {} samples", status.overruns);
+    }
+    if !status.committed.is_empty() || !status.tentative.is_empty() {
+        println!("text:     {} {}", status.committed, status.tentative);
+    }
+    Ok(())
+}
+
+fn models(command: ModelsCommand) -> Result<(), String> {
+    match command {
+        ModelsCommand::List => {
+            let downloaded = whisper::get_downloaded_models();
+            for model in whisper::get_available_models() {
+                let marker = if downloaded.contains(&model.filename) {
+                    "*"
+                } else {
+                    " "
+                };
+                println!(
+                    "{} {:<26} {:>6} MB  {}",
+                    marker, model.filename, model.size_mb, model.description
+                );
+            }
+            println!(
+                "\n* downloaded to {}",
+                whisper::get_models_directory().display()
+            );
+            Ok(())
+        }
+        ModelsCommand::Download { name } => {
+            let model = whisper::find_model(&name).ok_or_else(|| {
+                format!("Unknown model '{}', see `hyprwhisper models list`", name)
+            })?;
+
+            let runtime = tokio::runtime::Runtime::new().map_err(|e| e.to_string())?;
+            runtime.block_on(whisper::download_model(&model, |downloaded, total| {
+                if let Some(percent) = (downloaded * 100).checked_div(total) {
+                    eprint!("\rDownloading {}: {:>3}%", model.filename, percent);
+                }
+            }))?;
+            eprintln!(
+                "\rDownloaded {} to {}",
+                model.filename,
+                whisper::get_models_directory().display()
+            );
+            Ok(())
+        }
+        ModelsCommand::Delete { name } => {
+            let filename = whisper::find_model(&name)
+                .map(|m| m.filename)
+                .unwrap_or(name);
+            whisper::delete_model(&filename)
+        }
+    }
+}
+
+fn transcribe(
+    file: &Path,
+    model: Option<String>,
+    language: Option<String>,
+    format: Option<OutputFormat>,
+    output: Option<PathBuf>,
+) -> Result<(), String> {
+    let format = output_format(format, output.as_deref());
+
+    let settings = load_settings();
+
+    let model_filename = match model {
+        Some(name) => whisper::find_model(&name)
+            .map(|m| m.filename)
+            .unwrap_or(name),
+        None => settings.model_filename.clone(),
+    };
+    let language = match language.as_deref() {
+        Some("auto") => None,
+        Some(language) => Some(language.to_string()),
+        None => settings.whisper_language(),
+    };
+
+    let audio = audio_file::decode_file(file)?;
+    eprintln!(
+        "Transcribing {:?} ({:.1}s, {} Hz, {} channel(s)) with {}",
+        file,
+        audio.duration_ms() as f64 / 1000.0,
+        audio.sample_rate,
+        audio.channels,
+        model_filename
+    );
+
+    let mut engine = WhisperEngine::new();
+    engine.load_model(whisper::model_path(&model_filename)?)?;
+
+    let mut transcript = engine.transcribe_detailed(
+        &audio.to_whisper(),
+        language.as_deref(),
+        &settings.whisper_prompt(),
+    )?;
+
+    let corrections = Replacements::new(&settings.corrections, "Corrections");
+    for segment in &mut transcript.segments {
+        let corrected = corrections.apply(&segment.text);
+        // Tokens can't be corrected one by one; drop them rather than have
+        // them contradict the text
+        if corrected != segment.text {
+            segment.text = corrected;
+            segment.tokens.clear();
+        }
+    }
+
+    write_output(&transcript.render(format), output)
+}
+
+/// `format`, else the one `output`'s extension names, else plain text
+fn output_format(format: Option<OutputFormat>, output: Option<&Path>) -> OutputFormat {
+    format
+        .or_else(|| {
+            output
+                .and_then(|p| p.extension())
+                .and_then(|e| OutputFormat::from_extension(&e.to_string_lossy()))
+        })
+        .unwrap_or_default()
+}
+
+/// Write `rendered` to `output`, or stdout
+fn write_output(rendered: &str, output: Option<PathBuf>) -> Result<(), String> {
+    match output {
+        Some(path) => std::fs::write(&path, rendered)
+            .map_err(|e| format!("Failed to write {:?}: {}", path, e)),
+        None => {
+            print!("{}", rendered);
+            if !rendered.ends_with('\n') {
+                println!();
+            }
+            Ok(())
+        }
+    }
+}
+
+fn vocabulary(command: VocabularyCommand) -> Result<(), String> {
+    match command {
+        VocabularyCommand::List => {
+            let settings = load_settings();
+
+            for term in &settings.vocabulary {
+                println!("{}", term);
+            }
+            if !settings.corrections.is_empty() {
+                println!();
+                for correction in &settings.corrections {
+                    println!("{} -> {}", correction.from, correction.to);
+                }
+            }
+            Ok(())
+        }
+        VocabularyCommand::Add { term, heard } => {
+            settings::add_vocabulary_term(&term, heard.as_deref())
+        }
+    }
+}
+
+/// Run a transcript through the same steps as the app's `render_text`
+fn dry_run(text: Option<String>, profile: Option<String>) -> Result<(), String> {
+    let text = match text {
+        Some(text) => text,
+        None => std::io::read_to_string(std::io::stdin()).map_err(|e| e.to_string())?,
+    };
+
+    let mut settings = load_settings();
+
+    let profile = match profile {
+        Some(name) => Some(
+            settings
+                .profiles
+                .iter()
+                .find(|p| p.name == name)
+                .cloned()
+                .ok_or_else(|| format!("No profile named '{}'", name))?,
+        ),
+        None => None,
+    };
+    if let Some(profile) = &profile {
+        profile.apply(&mut settings);
+    }
+
+    let voice_commands = if settings.voice_commands {
+        VoiceCommands::new(&settings.language, &settings.custom_voice_commands)
+    } else {
+        VoiceCommands::disabled()
+    };
+    let text_rules = profile.as_ref().map(TextRules::new).unwrap_or_default();
+
+    let show = |label: &str, before: &str, after: &str| {
+        if before != after {
+            eprintln!("{:<16} {:?}", label, after);
+        }
+    };
+
+    eprintln!("{:<16} {:?}", "transcript", text);
+    let corrected = Replacements::new(&settings.corrections, "Corrections").apply(&text);
+    show("corrections", &text, &corrected);
+    let rendered = voice_commands.render(&corrected);
+    show("voice commands", &corrected, &rendered);
+    let ruled = text_rules.apply(&rendered);
+    show("profile", &rendered, &ruled);
+
+    let rules = Rules::new(&settings.rules, chrono::Local::now());
+    let mut output = ruled;
+    for (index, after) in rules.trace(&output) {
+        eprintln!("{:<16} {:?}", format!("rule {}", index + 1), after);
+        output = after;
+    }
+
+    println!("{}", output);
+    Ok(())
+}
+
+fn history(command: HistoryCommand) -> Result<(), String> {
+    match command {
+        HistoryCommand::List { limit, json } => print_entries(history::load()?, limit, json),
+        HistoryCommand::Search { query, limit, json } => {
+            let entries = history::load()?
+                .into_iter()
+                .filter(|entry| entry.matches(&query))
+                .collect();
+            print_entries(entries, limit, json)
+        }
+        HistoryCommand::Copy { id } => clipboard::copy(&history_entry(id)?.text, false),
+        HistoryCommand::Type { id } => {
+            let entry = history_entry(id)?;
+            let settings = load_settings();
+            InjectorChain::from_names(&settings.text_injectors)
+                .edit(0, &entry.text)
+                .map(|_| ())
+        }
+        HistoryCommand::Export { id, format, output } => {
+            let format = output_format(format, output.as_deref());
+            let entry = history_entry(id)?;
+            let rendered = match format {
+                // Plain text is what was typed, even without timings
+                OutputFormat::Txt => entry.text.clone(),
+                _ => entry.transcript()?.render(format),
+            };
+            write_output(&rendered, output)
+        }
+        HistoryCommand::Clear => history::clear(),
+    }
+}
+
+/// Entry `id`, or the latest one
+fn history_entry(id: Option<u64>) -> Result<Entry, String> {
+    match id {
+        Some(id) => history::find(id),
+        None => history::load()?
+            .pop()
+            .ok_or_else(|| "The history is empty".to_string()),
+    }
+}
+
+/// Print the last `limit` entries, oldest first so the newest ends up
+/// next to the prompt
+fn print_entries(entries: Vec<Entry>, limit: usize, json: bool) -> Result<(), String> {
+    let skip = entries.len().saturating_sub(limit);
+
+    for entry in &entries[skip..] {
+        if json {
+            println!(
+                "{}",
+                serde_json::to_string(entry).map_err(|e| e.to_string())?
+            );
+            continue;
+        }
+
+        let preview: String = entry.text.replace('\n', " ").chars().take(60).collect();
+        let ellipsis = if entry.text.chars().count() > 60 {
+            "..."
+        } else {
+            ""
+        };
+        println!(
+            "{:>5}{} {}  {:<16} {}{}",
+            entry.id,
+            if entry.error.is_some() { "!" } else { " " },
+            entry.timestamp.format("%Y-%m-%d %H:%M"),
+            entry.window_class.as_deref().unwrap_or("-"),
+            preview,
+            ellipsis
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,295 @@
+//! Wayland clipboard and primary selection through wl-clipboard.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Preferred when the selection is offered in several types
+const TEXT_MIME: &str = "text/plain;charset=utf-8";
+
+/// Put `text` on the clipboard, or on the primary selection if `primary`
+pub fn copy(text: &str, primary: bool) -> Result<(), String> {
+    Clipboard::new().copy(text, primary)
+}
+
+/// Save the current clipboard (or primary selection) so it can be restored
+pub fn snapshot(primary: bool) -> Result<Snapshot, String> {
+    Clipboard::new().snapshot(primary)
+}
+
+/// The wl-copy and wl-paste programs to run
+#[derive(Debug, Clone)]
+pub struct Clipboard {
+    pub copy_program: String,
+    pub paste_program: String,
+}
+
+impl Clipboard {
+    pub fn new() -> Self {
+        Self::with_programs("wl-copy", "wl-paste")
+    }
+
+    /// Use other programs in place of `wl-copy` and `wl-paste`
+    pub fn with_programs(copy: impl Into<String>, paste: impl Into<String>) -> Self {
+        Self {
+            copy_program: copy.into(),
+            paste_program: paste.into(),
+        }
+    }
+
+    /// Put `text` on the clipboard, or on the primary selection if `primary`
+    pub fn copy(&self, text: &str, primary: bool) -> Result<(), String> {
+        self.copy_bytes(text.as_bytes(), None, primary)
+    }
+
+    /// Save the current clipboard (or primary selection) so it can be restored
+    pub fn snapshot(&self, primary: bool) -> Result<Snapshot, String> {
+        let types = match self.paste(&["--list-types"], primary) {
+            Ok(types) => String::from_utf8_lossy(&types).into_owned(),
+            // wl-paste fails when nothing is copied
+            Err(_) => {
+                return Ok(Snapshot {
+                    clipboard: self.clone(),
+                    primary,
+                    contents: None,
+                })
+            }
+        };
+
+        let mime = types
+            .lines()
+            .find(|t| *t == TEXT_MIME)
+            .or_else(|| types.lines().next())
+            .map(str::to_string);
+
+        let contents = match mime {
+            Some(mime) => Some((
+                mime.clone(),
+                self.paste(&["--no-newline", "--type", &mime], primary)?,
+            )),
+            None => None,
+        };
+
+        Ok(Snapshot {
+            clipboard: self.clone(),
+            primary,
+            contents,
+        })
+    }
+
+    /// Empty the clipboard (or primary selection)
+    fn clear(&self, primary: bool) -> Result<(), String> {
+        let mut command = Command::new(&self.copy_program);
+        command.arg("--clear");
+        if primary {
+            command.arg("--primary");
+        }
+        let status = command
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .map_err(|e| format!("Failed to run {}: {}", self.copy_program, e))?;
+        if !status.success() {
+            return Err(format!(
+                "{} --clear exited with {}",
+                self.copy_program, status
+            ));
+        }
+        Ok(())
+    }
+
+    /// Hand `data` to wl-copy on stdin. wl-copy forks to keep serving the
+    /// selection, so its output is discarded rather than waited on.
+    fn copy_bytes(&self, data: &[u8], mime: Option<&str>, primary: bool) -> Result<(), String> {
+        let mut command = Command::new(&self.copy_program);
+        if primary {
+            command.arg("--primary");
+        }
+        if let Some(mime) = mime {
+            command.args(["--type", mime]);
+        }
+
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("Failed to run {}: {}", self.copy_program, e))?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin
+                .write_all(data)
+                .map_err(|e| format!("Failed to write to {}: {}", self.copy_program, e))?;
+        }
+
+        let status = child
+            .wait()
+            .map_err(|e| format!("Failed to wait for {}: {}", self.copy_program, e))?;
+        if !status.success() {
+            return Err(format!("{} exited with {}", self.copy_program, status));
+        }
+        Ok(())
+    }
+
+    fn paste(&self, args: &[&str], primary: bool) -> Result<Vec<u8>, String> {
+        let mut command = Command::new(&self.paste_program);
+        if primary {
+            command.arg("--primary");
+        }
+        let output = command
+            .args(args)
+            .stdin(Stdio::null())
+            .output()
+            .map_err(|e| format!("Failed to run {}: {}", self.paste_program, e))?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(format!("{} failed: {}", self.paste_program, stderr.trim()));
+        }
+        Ok(output.stdout)
+    }
+}
+
+impl Default for Clipboard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// What was on the clipboard before we replaced it
+pub struct Snapshot {
+    clipboard: Clipboard,
+    primary: bool,
+    /// MIME type and data, `None` if the selection was empty
+    contents: Option<(String, Vec<u8>)>,
+}
+
+impl Snapshot {
+    /// Put the saved contents back, clearing the selection if it was empty
+    pub fn restore(self) -> Result<(), String> {
+        match self.contents {
+            Some((mime, data)) => self.clipboard.copy_bytes(&data, Some(&mime), self.primary),
+            None => self.clipboard.clear(self.primary),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inject::tests::{calls, fake, SERIAL};
+    use std::path::Path;
+
+    /// wl-copy that saves what it is given to `wl-copy.stdin`
+    fn fake_copy(dir: &Path) -> String {
+        let stdin = dir.join("wl-copy.stdin");
+        fake(dir, "wl-copy", &format!("cat >> '{}'", stdin.display()))
+    }
+
+    fn copied(dir: &Path) -> String {
+        std::fs::read_to_string(dir.join("wl-copy.stdin")).unwrap_or_default()
+    }
+
+    #[test]
+    fn copy_pipes_the_text_to_wl_copy() {
+        let _serial = SERIAL.lock().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let clipboard = Clipboard::with_programs(fake_copy(dir.path()), "/nonexistent/wl-paste");
+
+        clipboard.copy("héllo\n", false).unwrap();
+        clipboard.copy("selected", true).unwrap();
+
+        assert_eq!(calls(dir.path(), "wl-copy"), ["", "[--primary]"]);
+        assert_eq!(copied(dir.path()), "héllo\nselected");
+    }
+
+    #[test]
+    fn copy_fails_when_wl_copy_does() {
+        let _serial = SERIAL.lock().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let clipboard = Clipboard::with_programs(
+            fake(dir.path(), "wl-copy", "cat > /dev/null; exit 1"),
+            "/nonexistent/wl-paste",
+        );
+        assert!(clipboard.copy("x", false).is_err());
+
+        let missing = Clipboard::with_programs("/nonexistent/wl-copy", "/nonexistent/wl-paste");
+        assert!(missing
+            .copy("x", false)
+            .unwrap_err()
+            .starts_with("Failed to run /nonexistent/wl-copy"));
+    }
+
+    #[test]
+    fn snapshot_saves_the_text_type_and_restore_puts_it_back() {
+        let _serial = SERIAL.lock().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let paste = fake(
+            dir.path(),
+            "wl-paste",
+            "case \"$*\" in\n\
+             *--list-types*) printf 'text/html\\ntext/plain;charset=utf-8\\nTEXT\\n' ;;\n\
+             *) printf 'saved text' ;;\n\
+             esac",
+        );
+        let clipboard = Clipboard::with_programs(fake_copy(dir.path()), paste);
+
+        let snapshot = clipboard.snapshot(true).unwrap();
+        clipboard.copy("dictated", true).unwrap();
+        snapshot.restore().unwrap();
+
+        assert_eq!(
+            calls(dir.path(), "wl-paste"),
+            [
+                "[--primary][--list-types]",
+                "[--primary][--no-newline][--type][text/plain;charset=utf-8]",
+            ]
+        );
+        assert_eq!(
+            calls(dir.path(), "wl-copy"),
+            [
+                "[--primary]",
+                "[--primary][--type][text/plain;charset=utf-8]"
+            ]
+        );
+        assert_eq!(copied(dir.path()), "dictatedsaved text");
+    }
+
+    #[test]
+    fn snapshot_keeps_the_first_type_without_plain_text() {
+        let _serial = SERIAL.lock().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let paste = fake(
+            dir.path(),
+            "wl-paste",
+            "case \"$*\" in\n\
+             *--list-types*) printf 'image/png\\n' ;;\n\
+             *) printf 'PNG' ;;\n\
+             esac",
+        );
+        let clipboard = Clipboard::with_programs(fake_copy(dir.path()), paste);
+
+        clipboard.snapshot(false).unwrap().restore().unwrap();
+        assert_eq!(calls(dir.path(), "wl-copy"), ["[--type][image/png]"]);
+        assert_eq!(copied(dir.path()), "PNG");
+    }
+
+    #[test]
+    fn restoring_an_empty_clipboard_clears_it() {
+        let _serial = SERIAL.lock().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let paste = fake(
+            dir.path(),
+            "wl-paste",
+            "echo 'Nothing is copied' >&2; exit 1",
+        );
+        let clipboard = Clipboard::with_programs(fake_copy(dir.path()), paste);
+
+        let snapshot = clipboard.snapshot(false).unwrap();
+        clipboard.copy("dictated", false).unwrap();
+        snapshot.restore().unwrap();
+
+        assert_eq!(calls(dir.path(), "wl-paste"), ["[--list-types]"]);
+        assert_eq!(calls(dir.path(), "wl-copy"), ["", "[--clear]"]);
+    }
+}
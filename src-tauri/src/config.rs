@@ -0,0 +1,319 @@
+//! Hand-editable `~/.config/hyprwhisper/config.toml`.
+//!
+//! Keys are `Settings` field names (`model` also works for `model_filename`)
+//! and take precedence over what the GUI saved in settings.json.
+
+use notify::{RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use crate::settings::{self, Settings};
+
+/// Keys accepted in config.toml that are spelled differently in `Settings`
+const KEY_ALIASES: &[(&str, &str)] = &[("model", "model_filename")];
+
+pub fn get_config_path() -> PathBuf {
+    settings::get_config_directory().join("config.toml")
+}
+
+/// Apply config.toml (if present) on top of `base`
+pub fn overlay(base: Settings) -> Result<Settings, String> {
+    let path = get_config_path();
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(base),
+        Err(e) => return Err(format!("Failed to read {:?}: {}", path, e)),
+    };
+    overlay_text(base, &text)
+}
+
+/// Apply the contents of a config.toml on top of `base`
+fn overlay_text(base: Settings, text: &str) -> Result<Settings, String> {
+    let mut table: toml::Table = text.parse().map_err(|e| describe_error(text, &e))?;
+    for (alias, field) in KEY_ALIASES {
+        if let Some(value) = table.remove(*alias) {
+            table.insert(field.to_string(), value);
+        }
+    }
+
+    // Type-check against Settings straight from the text so errors keep
+    // their line numbers; the merge below would lose them
+    toml::from_str::<Settings>(text).map_err(|e| describe_error(text, &e))?;
+
+    let mut merged = serde_json::to_value(&base).map_err(|e| e.to_string())?;
+    let overrides = serde_json::to_value(&table).map_err(|e| e.to_string())?;
+
+    for key in overrides.as_object().into_iter().flat_map(|o| o.keys()) {
+        if merged.get(key).is_none() {
+            eprintln!("config.toml: ignoring unknown key '{}'", key);
+        }
+    }
+
+    merge(&mut merged, overrides);
+    serde_json::from_value(merged).map_err(|e| format!("config.toml: {}", e))
+}
+
+/// Settings as the app should start with: settings.json, then config.toml.
+/// Errors are returned alongside the best settings we could load so the
+/// caller can report them instead of silently running on defaults.
+pub fn load_layered() -> (Settings, Vec<String>) {
+    let (_, effective, errors) = load_saved_and_layered();
+    (effective, errors)
+}
+
+/// Like `load_layered`, but also returns settings.json on its own, which is
+/// what changes should be saved on top of
+pub fn load_saved_and_layered() -> (Settings, Settings, Vec<String>) {
+    let mut errors = Vec::new();
+
+    let saved = settings::load().unwrap_or_else(|e| {
+        errors.push(e);
+        Settings::default()
+    });
+
+    let effective = match overlay(saved.clone()) {
+        Ok(settings) => settings,
+        Err(e) => {
+            errors.push(e);
+            saved.clone()
+        }
+    };
+
+    (saved, effective, errors)
+}
+
+/// `saved` with the fields that differ between `before` and `after`. The GUI
+/// edits the effective settings; taking only what it changed keeps keys
+/// pinned in config.toml out of settings.json.
+pub fn apply_changes(
+    saved: &Settings,
+    before: &Settings,
+    after: &Settings,
+) -> Result<Settings, String> {
+    let mut saved = serde_json::to_value(saved).map_err(|e| e.to_string())?;
+    let before = serde_json::to_value(before).map_err(|e| e.to_string())?;
+    let after = serde_json::to_value(after).map_err(|e| e.to_string())?;
+
+    if let (Some(saved), Some(before), Some(after)) =
+        (saved.as_object_mut(), before.as_object(), after.as_object())
+    {
+        for (key, value) in after {
+            if before.get(key) != Some(value) {
+                saved.insert(key.clone(), value.clone());
+            }
+        }
+    }
+
+    serde_json::from_value(saved).map_err(|e| e.to_string())
+}
+
+/// `path` and, if it is a symlink (e.g. into a dotfiles repo), the file it
+/// points to. Editors write to the target, so that is where events show up.
+fn config_files(path: &Path) -> Vec<PathBuf> {
+    let mut files = vec![path.to_path_buf()];
+    if let Ok(target) = std::fs::canonicalize(path) {
+        if target != path {
+            files.push(target);
+        }
+    }
+    files
+}
+
+/// Watch config.toml and call `on_change` with the reloaded settings (or the
+/// error) after each edit. Runs for the lifetime of the process.
+pub fn watch(on_change: impl Fn(Result<Settings, String>) + Send + 'static) {
+    let dir = settings::get_config_directory();
+    let path = get_config_path();
+
+    thread::spawn(move || {
+        let (tx, rx) = mpsc::channel();
+
+        let mut watcher = match notify::recommended_watcher(tx) {
+            Ok(watcher) => watcher,
+            Err(e) => {
+                eprintln!("Failed to create config watcher: {}", e);
+                return;
+            }
+        };
+
+        // Watch the directory: editors often replace the file by renaming
+        std::fs::create_dir_all(&dir).ok();
+        if let Err(e) = watcher.watch(&dir, RecursiveMode::NonRecursive) {
+            eprintln!("Failed to watch {:?}: {}", dir, e);
+            return;
+        }
+        let mut watched_dirs = vec![dir];
+
+        loop {
+            // Re-resolved every time, since the symlink may be re-pointed
+            let files = config_files(&path);
+            for file_dir in files.iter().filter_map(|file| file.parent()) {
+                if watched_dirs.iter().any(|watched| watched == file_dir) {
+                    continue;
+                }
+                match watcher.watch(file_dir, RecursiveMode::NonRecursive) {
+                    Ok(()) => watched_dirs.push(file_dir.to_path_buf()),
+                    Err(e) => eprintln!("Failed to watch {:?}: {}", file_dir, e),
+                }
+            }
+
+            let Ok(event) = rx.recv() else { break };
+            let touches_config = match event {
+                Ok(event) => event.paths.iter().any(|p| files.contains(p)),
+                Err(e) => {
+                    eprintln!("Config watcher error: {}", e);
+                    false
+                }
+            };
+            if !touches_config {
+                continue;
+            }
+
+            // Let the editor finish writing, and collapse the burst of events
+            thread::sleep(Duration::from_millis(200));
+            while rx.try_recv().is_ok() {}
+
+            println!("config.toml changed, reloading");
+            on_change(settings::load().and_then(overlay));
+        }
+    });
+}
+
+fn merge(base: &mut serde_json::Value, overrides: serde_json::Value) {
+    match (base, overrides) {
+        (serde_json::Value::Object(base), serde_json::Value::Object(overrides)) => {
+            for (key, value) in overrides {
+                merge(base.entry(key).or_insert(serde_json::Value::Null), value);
+            }
+        }
+        (base, value) => *base = value,
+    }
+}
+
+fn describe_error(text: &str, error: &toml::de::Error) -> String {
+    match error.span() {
+        Some(span) => {
+            let line = text[..span.start.min(text.len())].matches('\n').count() + 1;
+            format!("config.toml line {}: {}", line, error.message())
+        }
+        None => format!("config.toml: {}", error.message()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn saved() -> Settings {
+        Settings {
+            language: "en".to_string(),
+            hotkey: "Super+D".to_string(),
+            ..Settings::default()
+        }
+    }
+
+    #[test]
+    fn config_toml_overrides_the_saved_settings() {
+        let settings =
+            overlay_text(saved(), "language = \"de\"\nmodel = \"ggml-small.bin\"\n").unwrap();
+        assert_eq!(settings.language, "de");
+        assert_eq!(settings.model_filename, "ggml-small.bin");
+        // Keys it doesn't mention come from settings.json
+        assert_eq!(settings.hotkey, "Super+D");
+
+        assert_eq!(overlay_text(saved(), "").unwrap().language, "en");
+    }
+
+    #[test]
+    fn config_toml_can_set_lists_and_tables() {
+        let text = r#"
+vocabulary = ["Hyprland", "Wayland"]
+
+[[profiles]]
+name = "Terminal"
+class = "kitty"
+"#;
+        let settings = overlay_text(saved(), text).unwrap();
+        assert_eq!(settings.vocabulary, ["Hyprland", "Wayland"]);
+        assert_eq!(settings.profiles.len(), 1);
+        assert_eq!(settings.profiles[0].name, "Terminal");
+    }
+
+    #[test]
+    fn unknown_keys_are_ignored() {
+        let settings = overlay_text(saved(), "no_such_key = 1\nlanguage = \"fr\"\n").unwrap();
+        assert_eq!(settings.language, "fr");
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        let bad_syntax = "language = \"de\"\nhotkey = \n";
+        assert!(overlay_text(saved(), bad_syntax)
+            .unwrap_err()
+            .starts_with("config.toml line 2:"));
+
+        let bad_type = "language = \"de\"\n\nauto_paste = \"yes\"\n";
+        assert!(overlay_text(saved(), bad_type)
+            .unwrap_err()
+            .starts_with("config.toml line 3:"));
+    }
+
+    #[test]
+    fn merge_replaces_values_and_recurses_into_objects() {
+        let mut base = json!({ "a": 1, "b": { "x": 1, "y": 2 }, "c": [1, 2] });
+        merge(
+            &mut base,
+            json!({ "b": { "y": 3, "z": 4 }, "c": [3], "d": true }),
+        );
+        assert_eq!(
+            base,
+            json!({ "a": 1, "b": { "x": 1, "y": 3, "z": 4 }, "c": [3], "d": true })
+        );
+    }
+
+    #[test]
+    fn keys_pinned_in_config_toml_stay_out_of_settings_json() {
+        let saved = saved();
+        let before = overlay_text(saved.clone(), "language = \"de\"\n").unwrap();
+
+        // The GUI edits the effective settings, where the pinned language shows
+        let mut after = before.clone();
+        after.hotkey = "Super+Space".to_string();
+
+        let to_save = apply_changes(&saved, &before, &after).unwrap();
+        assert_eq!(to_save.hotkey, "Super+Space");
+        assert_eq!(to_save.language, "en");
+    }
+
+    #[test]
+    fn a_changed_pinned_key_is_saved_as_it_was_edited() {
+        let saved = saved();
+        let before = overlay_text(saved.clone(), "language = \"de\"\n").unwrap();
+        let mut after = before.clone();
+        after.language = "fr".to_string();
+
+        assert_eq!(
+            apply_changes(&saved, &before, &after).unwrap().language,
+            "fr"
+        );
+    }
+
+    #[test]
+    fn symlinked_config_files_are_matched_on_their_target() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = std::fs::canonicalize(dir.path()).unwrap();
+        let dotfiles = root.join("dotfiles");
+        std::fs::create_dir(&dotfiles).unwrap();
+        let target = dotfiles.join("config.toml");
+        std::fs::write(&target, "").unwrap();
+
+        let link = root.join("config.toml");
+        assert_eq!(config_files(&link), std::slice::from_ref(&link));
+
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+        assert_eq!(config_files(&link), [link, target]);
+    }
+}
@@ -0,0 +1,322 @@
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::audio::CaptureFormat;
+
+// Socket path for single-instance control
+pub fn get_socket_path() -> PathBuf {
+    let runtime_dir = std::env::var("XDG_RUNTIME_DIR").unwrap_or_else(|_| "/tmp".to_string());
+    PathBuf::from(runtime_dir).join("hyprwhisper.sock")
+}
+
+/// One line of JSON sent to the running instance, e.g. `{"cmd":"toggle"}`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "cmd", rename_all = "kebab-case")]
+pub enum Request {
+    Start,
+    Stop,
+    Toggle,
+    Cancel,
+    Status,
+    SetLanguage {
+        language: String,
+    },
+    LoadModel {
+        model: String,
+    },
+    /// Delete the last dictation from the window it was typed into
+    UndoLast,
+}
+
+/// What the running instance is doing, as reported by `status`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Status {
+    pub recording: bool,
+    pub model: String,
+    pub model_loaded: bool,
+    pub language: String,
+    pub committed: String,
+    pub tentative: String,
+    /// Format of the current (or last) recording's input
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input: Option<CaptureFormat>,
+    /// Samples the current (or last) recording dropped because they
+    /// couldn't be moved out of the capture buffer in time
+    #[serde(default)]
+    pub overruns: u64,
+}
+
+/// One line of JSON sent back for every request
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Response {
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<Status>,
+}
+
+impl Response {
+    pub fn ok() -> Self {
+        Self {
+            ok: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Self {
+            ok: false,
+            error: Some(message.into()),
+            status: None,
+        }
+    }
+
+    pub fn status(status: Status) -> Self {
+        Self {
+            ok: true,
+            error: None,
+            status: Some(status),
+        }
+    }
+}
+
+impl From<Result<(), String>> for Response {
+    fn from(result: Result<(), String>) -> Self {
+        match result {
+            Ok(()) => Response::ok(),
+            Err(e) => Response::error(e),
+        }
+    }
+}
+
+/// Send a request to the running instance and wait for its reply.
+/// Fails if no instance is listening.
+pub fn send(request: &Request) -> Result<Response, String> {
+    let socket_path = get_socket_path();
+    let mut stream = UnixStream::connect(&socket_path)
+        .map_err(|e| format!("HyprWhisper is not running ({:?}: {})", socket_path, e))?;
+    stream.set_read_timeout(Some(Duration::from_secs(30))).ok();
+
+    let mut line = serde_json::to_string(request).map_err(|e| e.to_string())?;
+    line.push('\n');
+    stream
+        .write_all(line.as_bytes())
+        .map_err(|e| format!("Failed to send command: {}", e))?;
+
+    let mut reply = String::new();
+    BufReader::new(stream)
+        .read_line(&mut reply)
+        .map_err(|e| format!("Failed to read reply: {}", e))?;
+
+    serde_json::from_str(&reply).map_err(|e| format!("Invalid reply {:?}: {}", reply, e))
+}
+
+/// Serve the line-delimited JSON protocol on the control socket, calling
+/// `handler` for each request. Every connection gets its own thread and may
+/// send any number of requests.
+pub fn start_listener(handler: impl Fn(Request) -> Response + Send + Sync + 'static) {
+    let socket_path = get_socket_path();
+
+    // Remove existing socket file
+    let _ = std::fs::remove_file(&socket_path);
+
+    // Create new socket
+    let listener = match UnixListener::bind(&socket_path) {
+        Ok(l) => l,
+        Err(e) => {
+            eprintln!("Failed to create socket: {}", e);
+            return;
+        }
+    };
+
+    let handler = Arc::new(handler);
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let handler = Arc::clone(&handler);
+                    thread::spawn(move || {
+                        if let Err(e) = serve_connection(stream, handler.as_ref()) {
+                            eprintln!("Control connection error: {}", e);
+                        }
+                    });
+                }
+                Err(e) => {
+                    eprintln!("Socket error: {}", e);
+                    break;
+                }
+            }
+        }
+    });
+}
+
+/// Remove the socket file when the instance exits
+pub fn remove_socket() {
+    let _ = std::fs::remove_file(get_socket_path());
+}
+
+fn serve_connection(
+    stream: UnixStream,
+    handler: &(impl Fn(Request) -> Response + ?Sized),
+) -> std::io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 0 {
+        // Older builds send a bare "STOP" without a newline and hang up
+        // without reading a reply
+        if line.trim() == "STOP" {
+            println!("Received legacy stop signal");
+            handler(Request::Stop);
+            return Ok(());
+        }
+
+        if !line.trim().is_empty() {
+            let response = match serde_json::from_str::<Request>(line.trim()) {
+                Ok(request) => handler(request),
+                Err(e) => Response::error(format!("Invalid request: {}", e)),
+            };
+
+            let mut reply = serde_json::to_string(&response).unwrap_or_default();
+            reply.push('\n');
+            writer.write_all(reply.as_bytes())?;
+        }
+        line.clear();
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use std::net::Shutdown;
+    use std::sync::Mutex;
+
+    /// Serve one end of a socket pair, recording the requests handled
+    fn serve() -> (UnixStream, thread::JoinHandle<Vec<String>>) {
+        let (client, server) = UnixStream::pair().unwrap();
+        let thread = thread::spawn(move || {
+            let handled = Mutex::new(Vec::new());
+            serve_connection(server, &|request: Request| {
+                handled.lock().unwrap().push(format!("{:?}", request));
+                match request {
+                    Request::Status => Response::status(Status {
+                        language: "de".to_string(),
+                        ..Default::default()
+                    }),
+                    Request::LoadModel { model } => Response::error(format!("No model {}", model)),
+                    _ => Response::ok(),
+                }
+            })
+            .unwrap();
+            handled.into_inner().unwrap()
+        });
+        (client, thread)
+    }
+
+    fn read_reply(reader: &mut BufReader<UnixStream>) -> Response {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        serde_json::from_str(&line).unwrap()
+    }
+
+    #[test]
+    fn requests_get_one_reply_line_each() {
+        let (mut client, server) = serve();
+        client
+            .write_all(b"{\"cmd\":\"status\"}\n\n{\"cmd\":\"load-model\",\"model\":\"tiny\"}\n")
+            .unwrap();
+        client.shutdown(Shutdown::Write).unwrap();
+
+        let mut reader = BufReader::new(client);
+        let status = read_reply(&mut reader);
+        assert!(status.ok);
+        assert_eq!(status.status.unwrap().language, "de");
+
+        let error = read_reply(&mut reader);
+        assert!(!error.ok);
+        assert_eq!(error.error.as_deref(), Some("No model tiny"));
+
+        assert_eq!(
+            server.join().unwrap(),
+            ["Status", "LoadModel { model: \"tiny\" }"]
+        );
+    }
+
+    #[test]
+    fn requests_round_trip_through_send_format() {
+        let (mut client, server) = serve();
+        let request = Request::SetLanguage {
+            language: "fr".to_string(),
+        };
+        let mut line = serde_json::to_string(&request).unwrap();
+        assert_eq!(line, r#"{"cmd":"set-language","language":"fr"}"#);
+        line.push('\n');
+        client.write_all(line.as_bytes()).unwrap();
+        client.shutdown(Shutdown::Write).unwrap();
+
+        assert!(read_reply(&mut BufReader::new(client)).ok);
+        assert_eq!(server.join().unwrap(), ["SetLanguage { language: \"fr\" }"]);
+    }
+
+    #[test]
+    fn a_malformed_line_gets_an_error_and_the_connection_goes_on() {
+        let (mut client, server) = serve();
+        client
+            .write_all(b"{\"cmd\":\"fly\"}\nnot json\n{\"cmd\":\"toggle\"}\n")
+            .unwrap();
+        client.shutdown(Shutdown::Write).unwrap();
+
+        let mut reader = BufReader::new(client);
+        for _ in 0..2 {
+            let reply = read_reply(&mut reader);
+            assert!(!reply.ok);
+            assert!(reply.error.unwrap().starts_with("Invalid request"));
+        }
+        assert!(read_reply(&mut reader).ok);
+        assert_eq!(server.join().unwrap(), ["Toggle"]);
+    }
+
+    #[test]
+    fn legacy_stop_is_handled_without_a_reply() {
+        let (mut client, server) = serve();
+        client.write_all(b"STOP").unwrap();
+        client.shutdown(Shutdown::Write).unwrap();
+
+        assert_eq!(server.join().unwrap(), ["Stop"]);
+        let mut rest = Vec::new();
+        client.read_to_end(&mut rest).unwrap();
+        assert!(rest.is_empty());
+    }
+
+    #[test]
+    fn legacy_stop_split_across_writes_is_still_seen() {
+        let (mut client, server) = serve();
+        client.write_all(b"ST").unwrap();
+        thread::sleep(Duration::from_millis(50));
+        client.write_all(b"OP").unwrap();
+        client.shutdown(Shutdown::Write).unwrap();
+
+        assert_eq!(server.join().unwrap(), ["Stop"]);
+    }
+
+    #[test]
+    fn stop_inside_a_longer_line_is_not_legacy_stop() {
+        let (mut client, server) = serve();
+        client.write_all(b"STOPPED\n").unwrap();
+        client.shutdown(Shutdown::Write).unwrap();
+
+        assert!(!read_reply(&mut BufReader::new(client)).ok);
+        assert!(server.join().unwrap().is_empty());
+    }
+}
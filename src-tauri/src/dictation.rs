@@ -0,0 +1,296 @@
+use std::collections::VecDeque;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Default number of consecutive hypotheses that must agree before a word is committed
+pub const DEFAULT_AGREEMENT: usize = 2;
+
+/// What the dictation session currently believes was said
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct PartialTranscript {
+    pub committed: String,
+    pub tentative: String,
+}
+
+/// Keystrokes that turn what was typed into what should be there
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize)]
+pub struct Edit {
+    /// Characters to delete from the end of what was typed, counted like
+    /// backspaces: an emoji or a letter with combining accents is one
+    pub backspaces: usize,
+    pub text: String,
+}
+
+/// Live dictation state: which words are final, which may still change, and
+/// what has actually been typed into the target.
+///
+/// Words are committed with a local-agreement policy: a word becomes final
+/// once the last `agreement` hypotheses all contain it at the same position.
+/// Committed words are never retracted, even if Whisper later changes its mind.
+pub struct DictationSession {
+    agreement: usize,
+    history: VecDeque<Vec<String>>,
+    committed: Vec<String>,
+    tentative: Vec<String>,
+    typed: String,
+}
+
+impl DictationSession {
+    pub fn new(agreement: usize) -> Self {
+        let agreement = agreement.max(1);
+        Self {
+            agreement,
+            history: VecDeque::with_capacity(agreement),
+            committed: Vec::new(),
+            tentative: Vec::new(),
+            typed: String::new(),
+        }
+    }
+
+    pub fn committed(&self) -> String {
+        self.committed.join(" ")
+    }
+
+    pub fn tentative(&self) -> String {
+        self.tentative.join(" ")
+    }
+
+    pub fn partial(&self) -> PartialTranscript {
+        PartialTranscript {
+            committed: self.committed(),
+            tentative: self.tentative(),
+        }
+    }
+
+    /// Feed a hypothesis for the whole session so far. Returns true if new
+    /// words were committed.
+    pub fn push_hypothesis(&mut self, hypothesis: &str) -> bool {
+        let words: Vec<String> = hypothesis.split_whitespace().map(str::to_string).collect();
+
+        if self.history.len() == self.agreement {
+            self.history.pop_front();
+        }
+        self.history.push_back(words);
+
+        let before = self.committed.len();
+
+        if self.history.len() == self.agreement {
+            let agreed = self.agreed_len();
+            if agreed > before {
+                let newest = self.history.back().expect("history is non-empty");
+                self.committed.extend_from_slice(&newest[before..agreed]);
+            }
+        }
+
+        let newest = self.history.back().expect("history is non-empty");
+        self.tentative = newest
+            .get(self.committed.len()..)
+            .map(<[String]>::to_vec)
+            .unwrap_or_default();
+
+        self.committed.len() > before
+    }
+
+    /// Commit the final transcript. Words beyond what is already committed are
+    /// taken as-is; earlier words are kept as they were committed.
+    pub fn finish(&mut self, final_text: &str) {
+        let words: Vec<&str> = final_text.split_whitespace().collect();
+        if let Some(rest) = words.get(self.committed.len()..) {
+            self.committed.extend(rest.iter().map(|w| w.to_string()));
+        }
+        self.tentative.clear();
+        self.history.clear();
+    }
+
+    /// Text as it was last typed into the target
+    pub fn typed(&self) -> &str {
+        &self.typed
+    }
+
+    /// Edit that brings the target from what was typed to `output` (the
+    /// committed text after post-processing), `None` if nothing changed.
+    /// Usually this only appends, but a spoken "scratch that" deletes.
+    pub fn pending_edit(&self, output: &str) -> Option<Edit> {
+        let prefix = self
+            .typed
+            .grapheme_indices(true)
+            .zip(output.graphemes(true))
+            .find(|((_, a), b)| a != b)
+            .map(|((i, _), _)| i)
+            .unwrap_or_else(|| self.typed.len().min(output.len()));
+
+        // `prefix` is a grapheme boundary in both strings: they agree up to it
+        let edit = Edit {
+            backspaces: self.typed[prefix..].graphemes(true).count(),
+            text: output[prefix..].to_string(),
+        };
+        (edit != Edit::default()).then_some(edit)
+    }
+
+    /// Record that the target now shows `output`
+    pub fn mark_typed(&mut self, output: &str) {
+        self.typed = output.to_string();
+    }
+
+    /// Index just past the last word every hypothesis in the history agrees on,
+    /// never less than what is already committed
+    fn agreed_len(&self) -> usize {
+        let start = self.committed.len();
+        let shortest = self.history.iter().map(Vec::len).min().unwrap_or(0);
+
+        let mut end = start;
+        while end < shortest {
+            let word = normalize_word(&self.history[0][end]);
+            if self
+                .history
+                .iter()
+                .skip(1)
+                .all(|h| normalize_word(&h[end]) == word)
+            {
+                end += 1;
+            } else {
+                break;
+            }
+        }
+        end
+    }
+}
+
+fn normalize_word(word: &str) -> String {
+    word.to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session_after(agreement: usize, hypotheses: &[&str]) -> DictationSession {
+        let mut session = DictationSession::new(agreement);
+        for hypothesis in hypotheses {
+            session.push_hypothesis(hypothesis);
+        }
+        session
+    }
+
+    fn edit(backspaces: usize, text: &str) -> Option<Edit> {
+        Some(Edit {
+            backspaces,
+            text: text.to_string(),
+        })
+    }
+
+    #[test]
+    fn agreement_of_one_commits_every_hypothesis() {
+        let mut session = DictationSession::new(1);
+        assert!(session.push_hypothesis("hello world"));
+        assert_eq!(session.committed(), "hello world");
+        assert_eq!(session.tentative(), "");
+    }
+
+    #[test]
+    fn agreement_of_two_commits_the_shared_prefix() {
+        let mut session = DictationSession::new(2);
+        assert!(!session.push_hypothesis("hello word"));
+        assert_eq!(session.committed(), "");
+        assert_eq!(session.tentative(), "hello word");
+
+        assert!(session.push_hypothesis("Hello world how"));
+        assert_eq!(session.committed(), "Hello");
+        assert_eq!(session.tentative(), "world how");
+    }
+
+    #[test]
+    fn agreement_of_three_needs_three_hypotheses() {
+        let mut session = session_after(3, &["one two", "one two three"]);
+        assert_eq!(session.committed(), "");
+
+        assert!(session.push_hypothesis("one two three four"));
+        assert_eq!(session.committed(), "one two");
+        assert_eq!(session.tentative(), "three four");
+
+        // Only the last three count
+        assert!(session.push_hypothesis("one two three five"));
+        assert_eq!(session.committed(), "one two three");
+    }
+
+    #[test]
+    fn committed_words_are_never_retracted() {
+        let mut session = session_after(2, &["the cat sat", "the cat sat on"]);
+        assert_eq!(session.committed(), "the cat sat");
+
+        assert!(!session.push_hypothesis("a dog"));
+        assert!(!session.push_hypothesis("a dog stood up"));
+        assert_eq!(session.committed(), "the cat sat");
+        assert_eq!(session.tentative(), "up");
+    }
+
+    #[test]
+    fn finish_appends_words_past_the_committed_ones() {
+        let mut session = session_after(2, &["Hello world", "Hello world"]);
+        session.finish("hello world how are you");
+        assert_eq!(session.committed(), "Hello world how are you");
+        assert_eq!(session.tentative(), "");
+    }
+
+    #[test]
+    fn finish_with_fewer_words_keeps_what_was_committed() {
+        let mut session = session_after(1, &["one two three"]);
+        session.finish("one");
+        assert_eq!(session.committed(), "one two three");
+    }
+
+    #[test]
+    fn finish_with_different_words_only_takes_the_extra_tail() {
+        let mut session = session_after(1, &["one two"]);
+        session.finish("won too three");
+        assert_eq!(session.committed(), "one two three");
+    }
+
+    #[test]
+    fn pending_edit_appends() {
+        let mut session = DictationSession::new(1);
+        assert_eq!(session.pending_edit("Hello"), edit(0, "Hello"));
+
+        session.mark_typed("Hello");
+        assert_eq!(session.pending_edit("Hello world"), edit(0, " world"));
+        assert_eq!(session.pending_edit("Hello"), None);
+    }
+
+    #[test]
+    fn pending_edit_deletes() {
+        let mut session = DictationSession::new(1);
+        session.mark_typed("Hello world. Bye");
+        assert_eq!(session.pending_edit("Hello world."), edit(4, ""));
+        assert_eq!(session.pending_edit(""), edit(16, ""));
+        assert_eq!(session.pending_edit("Hello there"), edit(10, "there"));
+    }
+
+    #[test]
+    fn pending_edit_handles_multibyte_prefixes() {
+        let mut session = DictationSession::new(1);
+        session.mark_typed("café au");
+        assert_eq!(session.pending_edit("café olé"), edit(2, "olé"));
+
+        session.mark_typed("naïve");
+        assert_eq!(session.pending_edit("naïf"), edit(2, "f"));
+
+        // Diverging inside a multibyte character
+        session.mark_typed("é");
+        assert_eq!(session.pending_edit("è"), edit(1, "è"));
+    }
+
+    #[test]
+    fn pending_edit_counts_backspaces_per_grapheme() {
+        let mut session = DictationSession::new(1);
+        session.mark_typed("Hi 👍🏽");
+        assert_eq!(session.pending_edit("Hi"), edit(2, ""));
+
+        session.mark_typed("Hi 👩\u{200d}👩\u{200d}👧");
+        assert_eq!(session.pending_edit("Hi "), edit(1, ""));
+
+        // An accent added to the last letter replaces the whole letter
+        session.mark_typed("cafe");
+        assert_eq!(session.pending_edit("cafe\u{301}"), edit(1, "e\u{301}"));
+        session.mark_typed("cafe\u{301} au lait");
+        assert_eq!(session.pending_edit("caf"), edit(9, ""));
+    }
+}
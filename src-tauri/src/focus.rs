@@ -0,0 +1,168 @@
+use serde::{Deserialize, Serialize};
+
+use crate::hyprland::Event;
+
+/// Where dictated text goes when focus changes mid-dictation
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FocusPolicy {
+    /// Type into whichever window the user focused last
+    Follow,
+    /// Keep refocusing the window that was active when dictation started;
+    /// if it closes, type into whatever has focus
+    #[default]
+    Pin,
+    /// Like `Pin`, but hold the text back once the original window closes
+    Pause,
+}
+
+/// The window dictated text is aimed at
+pub struct FocusTracker {
+    target: Option<String>,
+    closed: bool,
+}
+
+impl FocusTracker {
+    pub fn new(target: Option<String>) -> Self {
+        Self {
+            target,
+            closed: false,
+        }
+    }
+
+    /// Address to focus before typing, `None` to type into the current focus
+    pub fn target(&self) -> Option<String> {
+        self.target.clone()
+    }
+
+    /// Whether typing should go ahead. Only `Pause` ever holds text back.
+    pub fn may_type(&self, policy: FocusPolicy) -> bool {
+        !(policy == FocusPolicy::Pause && self.closed)
+    }
+
+    /// Update the target for a Hyprland event. Focus moving to one of our own
+    /// windows should not be passed in.
+    pub fn handle_event(&mut self, event: &Event, policy: FocusPolicy) {
+        match event {
+            Event::ActiveWindow(Some(address)) if policy == FocusPolicy::Follow => {
+                self.target = Some(address.clone());
+                self.closed = false;
+            }
+            Event::CloseWindow(address) if self.target.as_ref() == Some(address) => {
+                println!("Target window {} closed", address);
+                self.closed = true;
+                if policy != FocusPolicy::Pause {
+                    self.target = None;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hyprland::parse_event;
+
+    const POLICIES: [FocusPolicy; 3] = [FocusPolicy::Follow, FocusPolicy::Pin, FocusPolicy::Pause];
+
+    fn tracker() -> FocusTracker {
+        FocusTracker::new(Some("0xa".to_string()))
+    }
+
+    fn focus(address: &str) -> Event {
+        Event::ActiveWindow(Some(address.to_string()))
+    }
+
+    fn close(address: &str) -> Event {
+        Event::CloseWindow(address.to_string())
+    }
+
+    #[test]
+    fn follow_retargets_on_focus_change() {
+        let mut tracker = tracker();
+        tracker.handle_event(&focus("0xb"), FocusPolicy::Follow);
+        assert_eq!(tracker.target().as_deref(), Some("0xb"));
+
+        // Focus on nothing (e.g. an empty workspace) keeps the last window
+        tracker.handle_event(&Event::ActiveWindow(None), FocusPolicy::Follow);
+        assert_eq!(tracker.target().as_deref(), Some("0xb"));
+        assert!(tracker.may_type(FocusPolicy::Follow));
+    }
+
+    #[test]
+    fn pin_and_pause_ignore_focus_changes() {
+        for policy in [FocusPolicy::Pin, FocusPolicy::Pause] {
+            let mut tracker = tracker();
+            tracker.handle_event(&focus("0xb"), policy);
+            tracker.handle_event(&Event::ActiveWindow(None), policy);
+            assert_eq!(tracker.target().as_deref(), Some("0xa"), "{:?}", policy);
+            assert!(tracker.may_type(policy));
+        }
+    }
+
+    #[test]
+    fn closing_the_target_under_follow_and_pin_types_into_the_current_focus() {
+        for policy in [FocusPolicy::Follow, FocusPolicy::Pin] {
+            let mut tracker = tracker();
+            tracker.handle_event(&close("0xa"), policy);
+            assert_eq!(tracker.target(), None, "{:?}", policy);
+            assert!(tracker.may_type(policy));
+        }
+    }
+
+    #[test]
+    fn closing_the_target_under_pause_holds_text_back() {
+        let mut tracker = tracker();
+        tracker.handle_event(&close("0xa"), FocusPolicy::Pause);
+        assert_eq!(tracker.target().as_deref(), Some("0xa"));
+        assert!(!tracker.may_type(FocusPolicy::Pause));
+
+        // Refocusing something else doesn't resume a paused dictation
+        tracker.handle_event(&focus("0xb"), FocusPolicy::Pause);
+        assert!(!tracker.may_type(FocusPolicy::Pause));
+    }
+
+    #[test]
+    fn follow_resumes_once_focus_moves_to_another_window() {
+        let mut tracker = tracker();
+        tracker.handle_event(&close("0xa"), FocusPolicy::Follow);
+        tracker.handle_event(&focus("0xc"), FocusPolicy::Follow);
+        assert_eq!(tracker.target().as_deref(), Some("0xc"));
+        assert!(tracker.may_type(FocusPolicy::Follow));
+    }
+
+    #[test]
+    fn closing_another_window_changes_nothing() {
+        for policy in POLICIES {
+            let mut tracker = tracker();
+            tracker.handle_event(&close("0xb"), policy);
+            assert_eq!(tracker.target().as_deref(), Some("0xa"), "{:?}", policy);
+            assert!(tracker.may_type(policy));
+        }
+    }
+
+    #[test]
+    fn workspace_events_change_nothing() {
+        for policy in POLICIES {
+            let mut tracker = tracker();
+            for line in ["workspace>>2", "workspacev2>>2,2", "focusedmon>>DP-1,2"] {
+                if let Some(event) = parse_event(line) {
+                    tracker.handle_event(&event, policy);
+                }
+            }
+            assert_eq!(tracker.target().as_deref(), Some("0xa"), "{:?}", policy);
+            assert!(tracker.may_type(policy));
+        }
+    }
+
+    #[test]
+    fn without_a_target_text_goes_to_the_current_focus() {
+        for policy in POLICIES {
+            let mut tracker = FocusTracker::new(None);
+            tracker.handle_event(&close("0xa"), policy);
+            assert!(tracker.may_type(policy), "{:?}", policy);
+        }
+    }
+}
@@ -0,0 +1,391 @@
+//! Log of finished dictations, so a transcript survives a failed paste or the
+//! wrong window having focus. Stored as JSON lines in
+//! `~/.local/share/hyprwhisper/history.jsonl`, oldest first.
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::transcript::{Segment, Transcript};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    /// Increasing, and never reused after pruning or `clear`; assigned by
+    /// `append`
+    pub id: u64,
+    pub timestamp: DateTime<Local>,
+    /// Class of the window the text was meant for
+    pub window_class: Option<String>,
+    /// Hyprland address of that window, to check focus before an undo
+    #[serde(default)]
+    pub window_address: Option<String>,
+    pub model: String,
+    pub language: String,
+    pub duration_ms: u64,
+    /// The text as it was (or should have been) typed
+    pub text: String,
+    /// Whisper's timed transcript, before voice commands and rules, for
+    /// exporting captions. Empty for entries saved by older versions.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub segments: Vec<Segment>,
+    /// Why delivery failed, `None` if the text arrived
+    #[serde(default)]
+    pub error: Option<String>,
+    /// All of `text` went in as keystrokes, so backspaces can take it back
+    #[serde(default)]
+    pub typed: bool,
+    /// Deleted again with `undo-last`
+    #[serde(default)]
+    pub undone: bool,
+}
+
+impl Entry {
+    /// Case-insensitive match on the text or window class
+    pub fn matches(&self, query: &str) -> bool {
+        let query = query.to_lowercase();
+        self.text.to_lowercase().contains(&query)
+            || self
+                .window_class
+                .as_ref()
+                .is_some_and(|class| class.to_lowercase().contains(&query))
+    }
+
+    /// The timed transcript, for `Transcript::render`
+    pub fn transcript(&self) -> Result<Transcript, String> {
+        if self.segments.is_empty() {
+            return Err(format!(
+                "History entry {} has no timings to export",
+                self.id
+            ));
+        }
+        Ok(Transcript {
+            language: Some(self.language.clone()).filter(|language| language != "auto"),
+            segments: self.segments.clone(),
+        })
+    }
+}
+
+/// How much history to keep; 0 means no limit
+#[derive(Debug, Clone, Copy)]
+pub struct Retention {
+    pub max_entries: usize,
+    pub max_age_days: u32,
+}
+
+pub fn get_history_path() -> PathBuf {
+    dirs::data_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("hyprwhisper")
+        .join("history.jsonl")
+}
+
+/// Where `clear` keeps the last id handed out, so ids stay unique
+fn last_id_path(path: &Path) -> PathBuf {
+    path.with_extension("last-id")
+}
+
+/// All entries, oldest first. A missing file is an empty history; lines
+/// that don't parse are reported and skipped.
+pub fn load() -> Result<Vec<Entry>, String> {
+    load_from(&get_history_path())
+}
+
+fn load_from(path: &Path) -> Result<Vec<Entry>, String> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("Failed to read {:?}: {}", path, e)),
+    };
+
+    Ok(contents
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .filter_map(|(number, line)| match serde_json::from_str(line) {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                eprintln!("{:?} line {}: {}", path, number + 1, e);
+                None
+            }
+        })
+        .collect())
+}
+
+pub fn find(id: u64) -> Result<Entry, String> {
+    load()?
+        .into_iter()
+        .find(|entry| entry.id == id)
+        .ok_or_else(|| format!("No history entry {}", id))
+}
+
+/// Record `entry` under a new id, drop what `retention` no longer allows and
+/// return the id
+pub fn append(entry: Entry, retention: Retention) -> Result<u64, String> {
+    append_to(&get_history_path(), entry, retention)
+}
+
+fn append_to(path: &Path, mut entry: Entry, retention: Retention) -> Result<u64, String> {
+    let mut entries = load_from(path)?;
+
+    // Pruning always keeps the newest entry, so only a cleared history needs
+    // the mark
+    let last_id = match entries.last() {
+        Some(last) => last.id,
+        None => read_last_id(path)?,
+    };
+    entry.id = last_id + 1;
+    let id = entry.id;
+    entries.push(entry);
+
+    if retention.max_age_days > 0 {
+        let cutoff = Local::now() - chrono::Duration::days(retention.max_age_days as i64);
+        entries.retain(|e| e.timestamp >= cutoff);
+    }
+    if retention.max_entries > 0 && entries.len() > retention.max_entries {
+        entries.drain(..entries.len() - retention.max_entries);
+    }
+
+    save_to(path, &entries)?;
+    Ok(id)
+}
+
+/// Change entry `id` in place
+pub fn update(id: u64, change: impl FnOnce(&mut Entry)) -> Result<(), String> {
+    update_in(&get_history_path(), id, change)
+}
+
+fn update_in(path: &Path, id: u64, change: impl FnOnce(&mut Entry)) -> Result<(), String> {
+    let mut entries = load_from(path)?;
+    let entry = entries
+        .iter_mut()
+        .find(|entry| entry.id == id)
+        .ok_or_else(|| format!("No history entry {}", id))?;
+    change(entry);
+    save_to(path, &entries)
+}
+
+pub fn clear() -> Result<(), String> {
+    clear_at(&get_history_path())
+}
+
+fn clear_at(path: &Path) -> Result<(), String> {
+    let last_id = match load_from(path)?.last() {
+        Some(last) => last.id,
+        None => read_last_id(path)?,
+    };
+    if last_id > 0 {
+        let mark = last_id_path(path);
+        std::fs::write(&mark, last_id.to_string())
+            .map_err(|e| format!("Failed to write {:?}: {}", mark, e))?;
+    }
+    save_to(path, &[])
+}
+
+/// The id recorded by the last `clear`, 0 if there was none
+fn read_last_id(path: &Path) -> Result<u64, String> {
+    let mark = last_id_path(path);
+    match std::fs::read_to_string(&mark) {
+        Ok(contents) => contents
+            .trim()
+            .parse()
+            .map_err(|e| format!("Invalid {:?}: {}", mark, e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(0),
+        Err(e) => Err(format!("Failed to read {:?}: {}", mark, e)),
+    }
+}
+
+/// Rewrite the whole file atomically, like `settings::save`. The temp file
+/// is created owner-only, so the history stays private.
+fn save_to(path: &Path, entries: &[Entry]) -> Result<(), String> {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {:?}: {}", dir, e))?;
+
+    let mut contents = Vec::new();
+    for entry in entries {
+        serde_json::to_writer(&mut contents, entry)
+            .map_err(|e| format!("Failed to serialize history: {}", e))?;
+        contents.push(b'\n');
+    }
+
+    let mut file = tempfile::NamedTempFile::new_in(dir)
+        .map_err(|e| format!("Failed to create temp file: {}", e))?;
+    file.write_all(&contents)
+        .map_err(|e| format!("Failed to write history: {}", e))?;
+    file.as_file()
+        .sync_all()
+        .map_err(|e| format!("Failed to sync history: {}", e))?;
+    file.persist(path)
+        .map_err(|e| format!("Failed to save history: {}", e))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(text: &str) -> Entry {
+        Entry {
+            id: 7,
+            timestamp: Local::now(),
+            window_class: Some("kitty".to_string()),
+            window_address: None,
+            model: "ggml-base.bin".to_string(),
+            language: "auto".to_string(),
+            duration_ms: 1000,
+            text: text.to_string(),
+            segments: Vec::new(),
+            error: None,
+            typed: true,
+            undone: false,
+        }
+    }
+
+    #[test]
+    fn entries_export_their_timings() {
+        let mut entry = entry("Hello.");
+        assert_eq!(
+            entry.transcript().unwrap_err(),
+            "History entry 7 has no timings to export"
+        );
+
+        entry.segments.push(Segment {
+            start_ms: 0,
+            end_ms: 1200,
+            text: "hello period".to_string(),
+            probability: 0.8,
+            tokens: Vec::new(),
+        });
+        let transcript = entry.transcript().unwrap();
+        assert_eq!(transcript.language, None);
+        assert_eq!(
+            transcript.to_srt(),
+            "1\n00:00:00,000 --> 00:00:01,200\nhello period\n\n"
+        );
+    }
+
+    #[test]
+    fn entries_without_segments_still_load() {
+        let mut json = serde_json::to_value(entry("old")).unwrap();
+        assert!(json.get("segments").is_none());
+        json.as_object_mut().unwrap().remove("window_address");
+
+        let entry: Entry = serde_json::from_value(json).unwrap();
+        assert!(entry.segments.is_empty());
+        assert_eq!(entry.text, "old");
+    }
+
+    const KEEP_ALL: Retention = Retention {
+        max_entries: 0,
+        max_age_days: 0,
+    };
+
+    fn ids(path: &Path) -> Vec<u64> {
+        load_from(path).unwrap().iter().map(|e| e.id).collect()
+    }
+
+    #[test]
+    fn append_assigns_increasing_ids() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.jsonl");
+        assert!(load_from(&path).unwrap().is_empty());
+
+        assert_eq!(append_to(&path, entry("one"), KEEP_ALL).unwrap(), 1);
+        assert_eq!(append_to(&path, entry("two"), KEEP_ALL).unwrap(), 2);
+        let entries = load_from(&path).unwrap();
+        assert_eq!(entries[0].text, "one");
+        assert_eq!(entries[1].text, "two");
+    }
+
+    #[test]
+    fn ids_are_not_reused_after_clear() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.jsonl");
+        clear_at(&path).unwrap();
+
+        append_to(&path, entry("one"), KEEP_ALL).unwrap();
+        append_to(&path, entry("two"), KEEP_ALL).unwrap();
+        clear_at(&path).unwrap();
+        assert!(load_from(&path).unwrap().is_empty());
+        // Clearing an empty history keeps the mark
+        clear_at(&path).unwrap();
+
+        assert_eq!(append_to(&path, entry("three"), KEEP_ALL).unwrap(), 3);
+    }
+
+    #[test]
+    fn retention_keeps_the_newest_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.jsonl");
+        let retention = Retention {
+            max_entries: 2,
+            max_age_days: 0,
+        };
+        for text in ["one", "two", "three"] {
+            append_to(&path, entry(text), retention).unwrap();
+        }
+        assert_eq!(ids(&path), [2, 3]);
+    }
+
+    #[test]
+    fn retention_drops_entries_older_than_max_age() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.jsonl");
+        let mut old = entry("old");
+        old.timestamp = Local::now() - chrono::Duration::days(8);
+        let mut recent = entry("recent");
+        recent.timestamp = Local::now() - chrono::Duration::days(6);
+        append_to(&path, old, KEEP_ALL).unwrap();
+        append_to(&path, recent, KEEP_ALL).unwrap();
+
+        let retention = Retention {
+            max_entries: 0,
+            max_age_days: 7,
+        };
+        append_to(&path, entry("new"), retention).unwrap();
+        assert_eq!(ids(&path), [2, 3]);
+    }
+
+    #[test]
+    fn update_changes_one_entry() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.jsonl");
+        append_to(&path, entry("one"), KEEP_ALL).unwrap();
+        append_to(&path, entry("two"), KEEP_ALL).unwrap();
+
+        update_in(&path, 2, |entry| entry.undone = true).unwrap();
+        let entries = load_from(&path).unwrap();
+        assert!(!entries[0].undone);
+        assert!(entries[1].undone);
+
+        assert_eq!(
+            update_in(&path, 9, |entry| entry.undone = true).unwrap_err(),
+            "No history entry 9"
+        );
+        assert_eq!(load_from(&path).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn unparsable_lines_are_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.jsonl");
+        append_to(&path, entry("one"), KEEP_ALL).unwrap();
+        let mut contents = std::fs::read_to_string(&path).unwrap();
+        contents.insert_str(0, "not json\n\n");
+        std::fs::write(&path, contents).unwrap();
+        assert_eq!(ids(&path), [1]);
+    }
+
+    #[test]
+    fn matches_text_or_window_class_in_any_case() {
+        let mut entry = entry("Meeting NOTES");
+        assert!(entry.matches("notes"));
+        assert!(entry.matches("KITTY"));
+        assert!(!entry.matches("firefox"));
+
+        entry.window_class = None;
+        assert!(!entry.matches("kitty"));
+        assert!(entry.matches(""));
+    }
+}
@@ -0,0 +1,366 @@
+//! Minimal client for Hyprland's request socket (`.socket.sock`), the same
+//! channel hyprctl uses, and a reader for its event socket (`.socket2.sock`).
+
+use serde::Deserialize;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+#[derive(Debug, thiserror::Error)]
+pub enum HyprlandError {
+    #[error("Hyprland is not running (HYPRLAND_INSTANCE_SIGNATURE is not set)")]
+    NotRunning,
+    #[error("Failed to connect to Hyprland at {path:?}: {source}")]
+    Connect {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("Hyprland IPC failed: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Unexpected reply from Hyprland: {0}")]
+    InvalidReply(String),
+    #[error("Hyprland rejected '{command}': {reply}")]
+    Rejected { command: String, reply: String },
+}
+
+/// A window as reported by `j/activewindow` and `j/clients` (fields we use)
+#[derive(Debug, Clone, Deserialize)]
+pub struct Window {
+    pub address: String,
+    #[serde(default)]
+    pub class: String,
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub pid: i64,
+}
+
+/// Events from `.socket2.sock` that we act on
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    /// Focus moved; `None` when no window has focus
+    ActiveWindow(Option<String>),
+    CloseWindow(String),
+}
+
+/// Parse one `EVENT>>DATA` line, ignoring events we don't use
+pub fn parse_event(line: &str) -> Option<Event> {
+    let (name, data) = line.trim_end().split_once(">>")?;
+    match name {
+        "activewindowv2" => Some(Event::ActiveWindow(
+            (!data.is_empty() && data != ",").then(|| normalize_address(data)),
+        )),
+        "closewindow" => Some(Event::CloseWindow(normalize_address(data))),
+        _ => None,
+    }
+}
+
+/// Events carry bare hex addresses; requests and replies use `0x...`
+fn normalize_address(address: &str) -> String {
+    if address.starts_with("0x") {
+        address.to_string()
+    } else {
+        format!("0x{}", address)
+    }
+}
+
+/// Call `on_event` for each event from this Hyprland instance until the
+/// socket closes. Connecting happens up front so errors reach the caller.
+pub fn listen(on_event: impl Fn(Event) + Send + 'static) -> Result<(), HyprlandError> {
+    let path = instance_directory()?.join(".socket2.sock");
+    let stream =
+        UnixStream::connect(&path).map_err(|source| HyprlandError::Connect { path, source })?;
+
+    thread::spawn(move || {
+        for line in BufReader::new(stream).lines() {
+            match line {
+                Ok(line) => {
+                    if let Some(event) = parse_event(&line) {
+                        on_event(event);
+                    }
+                }
+                Err(e) => {
+                    eprintln!("Hyprland event socket error: {}", e);
+                    break;
+                }
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// Directory holding this Hyprland instance's sockets
+pub fn instance_directory() -> Result<PathBuf, HyprlandError> {
+    instance_directory_for(signature_from_env(), runtime_dir_from_env())
+}
+
+fn signature_from_env() -> Option<String> {
+    std::env::var("HYPRLAND_INSTANCE_SIGNATURE").ok()
+}
+
+fn runtime_dir_from_env() -> Option<PathBuf> {
+    std::env::var_os("XDG_RUNTIME_DIR").map(PathBuf::from)
+}
+
+/// Directory holding the sockets of the instance with `signature`. Hyprland
+/// 0.40+ uses `$XDG_RUNTIME_DIR/hypr`, older versions `/tmp/hypr`.
+fn instance_directory_for(
+    signature: Option<String>,
+    runtime_dir: Option<PathBuf>,
+) -> Result<PathBuf, HyprlandError> {
+    let signature = signature.ok_or(HyprlandError::NotRunning)?;
+
+    let candidates = runtime_dir
+        .map(|dir| dir.join("hypr"))
+        .into_iter()
+        .chain(std::iter::once(PathBuf::from("/tmp/hypr")))
+        .map(|dir| dir.join(&signature));
+
+    let mut first = None;
+    for dir in candidates {
+        if dir.join(".socket.sock").exists() {
+            return Ok(dir);
+        }
+        first.get_or_insert(dir);
+    }
+    // Neither exists; report the modern location in the connect error
+    first.ok_or(HyprlandError::NotRunning)
+}
+
+pub struct Client {
+    socket_path: PathBuf,
+}
+
+impl Client {
+    /// Client for the Hyprland instance this process runs under
+    pub fn from_env() -> Result<Self, HyprlandError> {
+        Self::from_signature(signature_from_env())
+    }
+
+    /// Client for the instance with `signature`, `NotRunning` without one
+    pub fn from_signature(signature: Option<String>) -> Result<Self, HyprlandError> {
+        let directory = instance_directory_for(signature, runtime_dir_from_env())?;
+        Ok(Self::new(directory.join(".socket.sock")))
+    }
+
+    /// Client for an explicit socket, e.g. a fake server in tests
+    pub fn new(socket_path: PathBuf) -> Self {
+        Self { socket_path }
+    }
+
+    /// Send one raw request (e.g. `j/clients`) and return the reply
+    pub fn request(&self, command: &str) -> Result<String, HyprlandError> {
+        let mut stream =
+            UnixStream::connect(&self.socket_path).map_err(|source| HyprlandError::Connect {
+                path: self.socket_path.clone(),
+                source,
+            })?;
+        stream.set_read_timeout(Some(Duration::from_secs(2)))?;
+
+        stream.write_all(command.as_bytes())?;
+
+        // Hyprland closes the connection after replying
+        let mut reply = Vec::new();
+        stream.read_to_end(&mut reply)?;
+        Ok(String::from_utf8_lossy(&reply).into_owned())
+    }
+
+    /// The focused window, `None` if nothing is focused
+    pub fn active_window(&self) -> Result<Option<Window>, HyprlandError> {
+        let reply = self.request("j/activewindow")?;
+        let value: serde_json::Value = serde_json::from_str(&reply)
+            .map_err(|e| HyprlandError::InvalidReply(format!("{} ({:?})", e, reply)))?;
+
+        // Hyprland replies `{}` when no window has focus
+        if value.get("address").is_none() {
+            return Ok(None);
+        }
+        serde_json::from_value(value)
+            .map(Some)
+            .map_err(|e| HyprlandError::InvalidReply(e.to_string()))
+    }
+
+    /// Look up a window by address, `None` if it no longer exists
+    pub fn window(&self, address: &str) -> Result<Option<Window>, HyprlandError> {
+        let reply = self.request("j/clients")?;
+        let windows: Vec<Window> = serde_json::from_str(&reply)
+            .map_err(|e| HyprlandError::InvalidReply(format!("{} ({:?})", e, reply)))?;
+        Ok(windows.into_iter().find(|w| w.address == address))
+    }
+
+    /// Run a dispatcher, e.g. `dispatch("focuswindow", "address:0x...")`
+    pub fn dispatch(&self, dispatcher: &str, args: &str) -> Result<(), HyprlandError> {
+        let command = format!("dispatch {} {}", dispatcher, args);
+        let reply = self.request(&command)?;
+        if reply.trim() != "ok" {
+            return Err(HyprlandError::Rejected {
+                command,
+                reply: reply.trim().to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    pub fn focus_window(&self, address: &str) -> Result<(), HyprlandError> {
+        self.dispatch("focuswindow", &format!("address:{}", address))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixListener;
+
+    /// Serve `replies` on a fresh socket, one per connection, and return a
+    /// client for it along with a handle yielding the requests received
+    fn fake_server(
+        replies: &[&str],
+    ) -> (Client, tempfile::TempDir, thread::JoinHandle<Vec<String>>) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".socket.sock");
+        let listener = UnixListener::bind(&path).unwrap();
+        let replies: Vec<String> = replies.iter().map(|r| r.to_string()).collect();
+
+        let server = thread::spawn(move || {
+            let mut requests = Vec::new();
+            for reply in replies {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut buffer = [0; 1024];
+                let read = stream.read(&mut buffer).unwrap();
+                requests.push(String::from_utf8_lossy(&buffer[..read]).into_owned());
+                stream.write_all(reply.as_bytes()).unwrap();
+            }
+            requests
+        });
+
+        (Client::new(path), dir, server)
+    }
+
+    #[test]
+    fn active_window_is_none_for_an_empty_object() {
+        let (client, _dir, server) = fake_server(&["{}"]);
+        assert!(client.active_window().unwrap().is_none());
+        assert_eq!(server.join().unwrap(), ["j/activewindow"]);
+    }
+
+    #[test]
+    fn active_window_parses_the_fields_we_use() {
+        let reply = r#"{"address": "0x55d0c0ffee", "class": "kitty", "title": "vim", "pid": 42, "floating": false}"#;
+        let (client, _dir, _server) = fake_server(&[reply]);
+
+        let window = client.active_window().unwrap().unwrap();
+        assert_eq!(window.address, "0x55d0c0ffee");
+        assert_eq!(window.class, "kitty");
+        assert_eq!(window.title, "vim");
+        assert_eq!(window.pid, 42);
+    }
+
+    #[test]
+    fn window_looks_up_clients_by_address() {
+        let reply = r#"[{"address": "0x1", "class": "a"}, {"address": "0x2", "class": "b"}]"#;
+        let (client, _dir, server) = fake_server(&[reply, reply]);
+
+        assert_eq!(client.window("0x2").unwrap().unwrap().class, "b");
+        assert!(client.window("0x3").unwrap().is_none());
+        assert_eq!(server.join().unwrap(), ["j/clients", "j/clients"]);
+    }
+
+    #[test]
+    fn garbage_reply_is_invalid() {
+        let (client, _dir, _server) = fake_server(&["unknown request"]);
+        assert!(matches!(
+            client.active_window(),
+            Err(HyprlandError::InvalidReply(_))
+        ));
+    }
+
+    #[test]
+    fn dispatch_accepts_ok() {
+        let (client, _dir, server) = fake_server(&["ok"]);
+        client.focus_window("0x1").unwrap();
+        assert_eq!(server.join().unwrap(), ["dispatch focuswindow address:0x1"]);
+    }
+
+    #[test]
+    fn dispatch_rejects_anything_but_ok() {
+        let (client, _dir, _server) = fake_server(&["Invalid dispatcher\n"]);
+        match client.dispatch("nonsense", "") {
+            Err(HyprlandError::Rejected { command, reply }) => {
+                assert_eq!(command, "dispatch nonsense ");
+                assert_eq!(reply, "Invalid dispatcher");
+            }
+            other => panic!("expected Rejected, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn missing_socket_is_a_connect_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let client = Client::new(dir.path().join(".socket.sock"));
+        assert!(matches!(
+            client.request("j/activewindow"),
+            Err(HyprlandError::Connect { .. })
+        ));
+    }
+
+    #[test]
+    fn missing_signature_is_not_running() {
+        assert!(matches!(
+            Client::from_signature(None),
+            Err(HyprlandError::NotRunning)
+        ));
+    }
+
+    #[test]
+    fn instance_directory_prefers_the_one_with_a_socket() {
+        let runtime = tempfile::tempdir().unwrap();
+        let modern = runtime.path().join("hypr").join("abc_123");
+
+        // Neither exists yet: the modern location is reported
+        let found = instance_directory_for(Some("abc_123".into()), Some(runtime.path().into()));
+        assert_eq!(found.unwrap(), modern);
+
+        std::fs::create_dir_all(&modern).unwrap();
+        std::fs::write(modern.join(".socket.sock"), "").unwrap();
+        let found = instance_directory_for(Some("abc_123".into()), Some(runtime.path().into()));
+        assert_eq!(found.unwrap(), modern);
+
+        // Without XDG_RUNTIME_DIR only the legacy location is left
+        let found = instance_directory_for(Some("abc_123".into()), None);
+        assert_eq!(found.unwrap(), PathBuf::from("/tmp/hypr/abc_123"));
+    }
+
+    #[test]
+    fn parses_focus_events() {
+        assert_eq!(
+            parse_event("activewindowv2>>,"),
+            Some(Event::ActiveWindow(None))
+        );
+        assert_eq!(
+            parse_event("activewindowv2>>"),
+            Some(Event::ActiveWindow(None))
+        );
+        assert_eq!(
+            parse_event("activewindowv2>>55d0c0ffee\n"),
+            Some(Event::ActiveWindow(Some("0x55d0c0ffee".to_string())))
+        );
+        assert_eq!(
+            parse_event("activewindowv2>>0x55d0c0ffee"),
+            Some(Event::ActiveWindow(Some("0x55d0c0ffee".to_string())))
+        );
+    }
+
+    #[test]
+    fn parses_close_events_and_ignores_the_rest() {
+        assert_eq!(
+            parse_event("closewindow>>abc123"),
+            Some(Event::CloseWindow("0xabc123".to_string()))
+        );
+        assert_eq!(parse_event("workspace>>2"), None);
+        assert_eq!(parse_event("activewindow>>kitty,vim"), None);
+        assert_eq!(parse_event("not an event"), None);
+    }
+}
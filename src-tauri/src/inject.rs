@@ -0,0 +1,532 @@
+//! Ways of getting dictated text into the focused window.
+//!
+//! Apart from `native` (see `virtual_keyboard`), backends shell out to a
+//! helper program found on `PATH`, so each one can be exercised with a fake
+//! executable of the same name.
+
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::Command;
+use std::thread;
+use std::time::Duration;
+
+use crate::clipboard::Clipboard;
+use crate::virtual_keyboard;
+
+/// Backend names accepted in `Settings::text_injectors`
+pub const INJECTOR_NAMES: &[&str] = &["native", "wtype", "ydotool", "clipboard", "stdout"];
+
+pub trait TextInjector: Send + Sync {
+    /// Name used in settings and logs
+    fn name(&self) -> &'static str;
+
+    /// Whether the backend's helper programs are installed
+    fn is_available(&self) -> bool;
+
+    fn inject(&self, text: &str) -> Result<(), String>;
+
+    /// Delete `count` characters before the cursor
+    fn backspace(&self, count: usize) -> Result<(), String>;
+}
+
+/// Types through the compositor's virtual keyboard protocol
+pub struct Wtype {
+    program: String,
+}
+
+impl Wtype {
+    pub fn new() -> Self {
+        Self::with_program("wtype")
+    }
+
+    /// Use `program` (a name on `PATH` or a path) instead of `wtype`
+    pub fn with_program(program: impl Into<String>) -> Self {
+        Self {
+            program: program.into(),
+        }
+    }
+}
+
+impl Default for Wtype {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TextInjector for Wtype {
+    fn name(&self) -> &'static str {
+        "wtype"
+    }
+
+    fn is_available(&self) -> bool {
+        find_program(&self.program).is_some()
+    }
+
+    fn inject(&self, text: &str) -> Result<(), String> {
+        run(&self.program, &["--", text])
+    }
+
+    fn backspace(&self, count: usize) -> Result<(), String> {
+        wtype_backspace(&self.program, count)
+    }
+}
+
+/// Types through uinput, which works on any compositor but needs ydotoold
+pub struct Ydotool {
+    program: String,
+}
+
+impl Ydotool {
+    pub fn new() -> Self {
+        Self::with_program("ydotool")
+    }
+
+    /// Use `program` (a name on `PATH` or a path) instead of `ydotool`
+    pub fn with_program(program: impl Into<String>) -> Self {
+        Self {
+            program: program.into(),
+        }
+    }
+}
+
+impl Default for Ydotool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TextInjector for Ydotool {
+    fn name(&self) -> &'static str {
+        "ydotool"
+    }
+
+    fn is_available(&self) -> bool {
+        find_program(&self.program).is_some()
+    }
+
+    fn inject(&self, text: &str) -> Result<(), String> {
+        run(&self.program, &["type", "--", text])
+    }
+
+    fn backspace(&self, count: usize) -> Result<(), String> {
+        ydotool_backspace(&self.program, count)
+    }
+}
+
+/// Copies the text with wl-copy and pastes it with a synthesized Ctrl+V,
+/// sent with wtype or, failing that, ydotool
+pub struct ClipboardPaste {
+    clipboard: Clipboard,
+    wtype_program: String,
+    ydotool_program: String,
+}
+
+impl ClipboardPaste {
+    pub fn new() -> Self {
+        Self::with_programs(Clipboard::new(), "wtype", "ydotool")
+    }
+
+    /// Use other programs in place of wl-clipboard, `wtype` and `ydotool`
+    pub fn with_programs(
+        clipboard: Clipboard,
+        wtype: impl Into<String>,
+        ydotool: impl Into<String>,
+    ) -> Self {
+        Self {
+            clipboard,
+            wtype_program: wtype.into(),
+            ydotool_program: ydotool.into(),
+        }
+    }
+
+    /// Press Ctrl+V in the focused window
+    pub fn send_paste(&self) -> Result<(), String> {
+        let wtype = run(
+            &self.wtype_program,
+            &["-M", "ctrl", "-k", "v", "-m", "ctrl"],
+        );
+        if wtype.is_ok() {
+            return wtype;
+        }
+        // Linux keycodes: 29 = left ctrl, 47 = v
+        run(
+            &self.ydotool_program,
+            &["key", "29:1", "47:1", "47:0", "29:0"],
+        )
+        .map_err(|e| format!("{}; {}", wtype.unwrap_err(), e))
+    }
+}
+
+impl Default for ClipboardPaste {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TextInjector for ClipboardPaste {
+    fn name(&self) -> &'static str {
+        "clipboard"
+    }
+
+    fn is_available(&self) -> bool {
+        find_program(&self.clipboard.copy_program).is_some()
+            && (find_program(&self.wtype_program).is_some()
+                || find_program(&self.ydotool_program).is_some())
+    }
+
+    fn inject(&self, text: &str) -> Result<(), String> {
+        self.clipboard.copy(text, false)?;
+        // Give the target a moment to see the new selection offer
+        thread::sleep(Duration::from_millis(50));
+        self.send_paste()
+    }
+
+    fn backspace(&self, count: usize) -> Result<(), String> {
+        wtype_backspace(&self.wtype_program, count)
+            .or_else(|_| ydotool_backspace(&self.ydotool_program, count))
+    }
+}
+
+/// Writes the text to our own stdout, for scripts and terminals
+pub struct Stdout;
+
+impl TextInjector for Stdout {
+    fn name(&self) -> &'static str {
+        "stdout"
+    }
+
+    fn is_available(&self) -> bool {
+        true
+    }
+
+    fn inject(&self, text: &str) -> Result<(), String> {
+        let mut stdout = std::io::stdout().lock();
+        stdout
+            .write_all(text.as_bytes())
+            .and_then(|_| stdout.flush())
+            .map_err(|e| format!("Failed to write to stdout: {}", e))
+    }
+
+    /// Erase the way terminals do: back, blank, back
+    fn backspace(&self, count: usize) -> Result<(), String> {
+        self.inject(&"\u{8} \u{8}".repeat(count))
+    }
+}
+
+pub fn injector_by_name(name: &str) -> Option<Box<dyn TextInjector>> {
+    match name {
+        "native" => Some(Box::new(virtual_keyboard::Native)),
+        "wtype" => Some(Box::new(Wtype::new())),
+        "ydotool" => Some(Box::new(Ydotool::new())),
+        "clipboard" => Some(Box::new(ClipboardPaste::new())),
+        "stdout" => Some(Box::new(Stdout)),
+        _ => None,
+    }
+}
+
+/// Backends in order of preference; the first one that succeeds wins
+pub struct InjectorChain {
+    injectors: Vec<Box<dyn TextInjector>>,
+}
+
+impl InjectorChain {
+    pub fn new(injectors: Vec<Box<dyn TextInjector>>) -> Self {
+        Self { injectors }
+    }
+
+    /// Build a chain from backend names, skipping (and reporting) unknown ones
+    pub fn from_names(names: &[String]) -> Self {
+        let injectors = names
+            .iter()
+            .filter_map(|name| {
+                let injector = injector_by_name(name);
+                if injector.is_none() {
+                    eprintln!(
+                        "Unknown text injector '{}', expected one of {}",
+                        name,
+                        INJECTOR_NAMES.join(", ")
+                    );
+                }
+                injector
+            })
+            .collect();
+        Self::new(injectors)
+    }
+
+    /// Delete `backspaces` characters and then type `text`, with the first
+    /// backend that works, and return its name. The error lists why every
+    /// backend failed.
+    pub fn edit(&self, backspaces: usize, text: &str) -> Result<&'static str, String> {
+        let mut failures = Vec::new();
+        let mut backspaces = backspaces;
+
+        for injector in &self.injectors {
+            if !injector.is_available() {
+                failures.push(format!("{}: not installed", injector.name()));
+                continue;
+            }
+
+            if backspaces > 0 {
+                if let Err(e) = injector.backspace(backspaces) {
+                    eprintln!("{} failed: {}", injector.name(), e);
+                    failures.push(format!("{}: {}", injector.name(), e));
+                    continue;
+                }
+                // Deleted; a fallback backend must not delete again
+                backspaces = 0;
+            }
+
+            if text.is_empty() {
+                return Ok(injector.name());
+            }
+            match injector.inject(text) {
+                Ok(()) => return Ok(injector.name()),
+                Err(e) => {
+                    eprintln!("{} failed: {}", injector.name(), e);
+                    failures.push(format!("{}: {}", injector.name(), e));
+                }
+            }
+        }
+
+        if failures.is_empty() {
+            return Err("No text injectors configured".to_string());
+        }
+        Err(format!("Could not type text ({})", failures.join("; ")))
+    }
+}
+
+/// Locate an executable on `PATH` (or check an explicit path)
+pub fn find_program(program: &str) -> Option<PathBuf> {
+    if program.contains('/') {
+        let path = PathBuf::from(program);
+        return is_executable(&path).then_some(path);
+    }
+
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path)
+        .map(|dir| dir.join(program))
+        .find(|candidate| is_executable(candidate))
+}
+
+fn is_executable(path: &std::path::Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::metadata(path)
+        .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+fn wtype_backspace(program: &str, count: usize) -> Result<(), String> {
+    let mut args = Vec::with_capacity(count * 2);
+    for _ in 0..count {
+        args.extend(["-k", "BackSpace"]);
+    }
+    run(program, &args)
+}
+
+fn ydotool_backspace(program: &str, count: usize) -> Result<(), String> {
+    // Linux keycode 14 = backspace
+    let mut args = vec!["key"];
+    for _ in 0..count {
+        args.extend(["14:1", "14:0"]);
+    }
+    run(program, &args)
+}
+
+fn run(program: &str, args: &[&str]) -> Result<(), String> {
+    let output = Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to run {}: {}", program, e))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("{} failed: {}", program, stderr.trim()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::Path;
+    use std::sync::Mutex;
+
+    /// Writing a script while another test forks can make running it fail
+    /// with "Text file busy", so tests that run fake programs take turns
+    pub(crate) static SERIAL: Mutex<()> = Mutex::new(());
+
+    /// Executable in `dir` that logs its arguments as one `[arg]...` line
+    /// per call, then runs `body`
+    pub(crate) fn fake(dir: &Path, name: &str, body: &str) -> String {
+        let path = dir.join(name);
+        let log = dir.join(format!("{}.log", name));
+        let script = format!(
+            "#!/bin/sh\nfor arg in \"$@\"; do printf '[%s]' \"$arg\"; done >> '{}'\necho >> '{}'\n{}\n",
+            log.display(),
+            log.display(),
+            body
+        );
+        std::fs::write(&path, script).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    pub(crate) fn calls(dir: &Path, name: &str) -> Vec<String> {
+        std::fs::read_to_string(dir.join(format!("{}.log", name)))
+            .unwrap_or_default()
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn wtype_arguments() {
+        let _serial = SERIAL.lock().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let wtype = Wtype::with_program(fake(dir.path(), "wtype", "exit 0"));
+
+        assert!(wtype.is_available());
+        wtype.inject("-n hello world").unwrap();
+        wtype.backspace(2).unwrap();
+
+        assert_eq!(
+            calls(dir.path(), "wtype"),
+            ["[--][-n hello world]", "[-k][BackSpace][-k][BackSpace]"]
+        );
+    }
+
+    #[test]
+    fn ydotool_arguments() {
+        let _serial = SERIAL.lock().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let ydotool = Ydotool::with_program(fake(dir.path(), "ydotool", "exit 0"));
+
+        ydotool.inject("héllo").unwrap();
+        ydotool.backspace(1).unwrap();
+
+        assert_eq!(
+            calls(dir.path(), "ydotool"),
+            ["[type][--][héllo]", "[key][14:1][14:0]"]
+        );
+    }
+
+    #[test]
+    fn clipboard_paste_falls_back_to_ydotool_for_ctrl_v() {
+        let _serial = SERIAL.lock().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let paste = ClipboardPaste::with_programs(
+            Clipboard::new(),
+            fake(dir.path(), "wtype", "echo 'no keyboard' >&2; exit 1"),
+            fake(dir.path(), "ydotool", "exit 0"),
+        );
+
+        paste.send_paste().unwrap();
+        assert_eq!(calls(dir.path(), "wtype"), ["[-M][ctrl][-k][v][-m][ctrl]"]);
+        assert_eq!(
+            calls(dir.path(), "ydotool"),
+            ["[key][29:1][47:1][47:0][29:0]"]
+        );
+    }
+
+    #[test]
+    fn clipboard_paste_copies_then_presses_ctrl_v() {
+        let _serial = SERIAL.lock().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let stdin = dir.path().join("stdin");
+        let clipboard = Clipboard::with_programs(
+            fake(
+                dir.path(),
+                "wl-copy",
+                &format!("cat > '{}'", stdin.display()),
+            ),
+            "/nonexistent/wl-paste",
+        );
+        let paste = ClipboardPaste::with_programs(
+            clipboard,
+            fake(dir.path(), "wtype", "exit 0"),
+            "/nonexistent/ydotool",
+        );
+
+        assert!(paste.is_available());
+        paste.inject("hello").unwrap();
+        assert_eq!(calls(dir.path(), "wl-copy"), [""]);
+        assert_eq!(std::fs::read_to_string(&stdin).unwrap(), "hello");
+        assert_eq!(calls(dir.path(), "wtype"), ["[-M][ctrl][-k][v][-m][ctrl]"]);
+
+        let without_wl_copy = ClipboardPaste::with_programs(
+            Clipboard::with_programs("/nonexistent/wl-copy", "/nonexistent/wl-paste"),
+            fake(dir.path(), "wtype", "exit 0"),
+            "/nonexistent/ydotool",
+        );
+        assert!(!without_wl_copy.is_available());
+    }
+
+    #[test]
+    fn non_zero_exit_is_an_error_with_stderr() {
+        let _serial = SERIAL.lock().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let wtype = Wtype::with_program(fake(dir.path(), "wtype", "echo 'no seat' >&2; exit 3"));
+
+        let error = wtype.inject("hi").unwrap_err();
+        assert!(error.contains("no seat"), "{}", error);
+    }
+
+    #[test]
+    fn missing_program_is_not_available() {
+        let wtype = Wtype::with_program("/nonexistent/wtype");
+        assert!(!wtype.is_available());
+        assert!(wtype.inject("hi").is_err());
+    }
+
+    #[test]
+    fn edit_falls_back_without_deleting_twice() {
+        let _serial = SERIAL.lock().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        // Backspaces work, typing doesn't
+        let wtype = fake(
+            dir.path(),
+            "wtype",
+            "if [ \"$1\" = -- ]; then echo 'cannot type' >&2; exit 1; fi",
+        );
+        let ydotool = fake(dir.path(), "ydotool", "exit 0");
+
+        let chain = InjectorChain::new(vec![
+            Box::new(Wtype::with_program("/nonexistent/wtype")),
+            Box::new(Wtype::with_program(wtype)),
+            Box::new(Ydotool::with_program(ydotool)),
+        ]);
+        assert_eq!(chain.edit(2, "hi").unwrap(), "ydotool");
+
+        assert_eq!(
+            calls(dir.path(), "wtype"),
+            ["[-k][BackSpace][-k][BackSpace]", "[--][hi]"]
+        );
+        assert_eq!(calls(dir.path(), "ydotool"), ["[type][--][hi]"]);
+    }
+
+    #[test]
+    fn edit_retries_backspaces_that_failed() {
+        let _serial = SERIAL.lock().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let wtype = fake(dir.path(), "wtype", "exit 1");
+        let ydotool = fake(dir.path(), "ydotool", "exit 0");
+
+        let chain = InjectorChain::new(vec![
+            Box::new(Wtype::with_program(wtype)),
+            Box::new(Ydotool::with_program(ydotool)),
+        ]);
+        assert_eq!(chain.edit(1, "").unwrap(), "ydotool");
+        assert_eq!(calls(dir.path(), "ydotool"), ["[key][14:1][14:0]"]);
+    }
+
+    #[test]
+    fn edit_reports_every_failure() {
+        let chain = InjectorChain::new(vec![Box::new(Wtype::with_program("/nonexistent/wtype"))]);
+        let error = chain.edit(0, "hi").unwrap_err();
+        assert!(error.contains("wtype: not installed"), "{}", error);
+
+        let error = InjectorChain::new(Vec::new()).edit(0, "hi").unwrap_err();
+        assert_eq!(error, "No text injectors configured");
+    }
+}
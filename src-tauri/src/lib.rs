@@ -1,189 +1,688 @@
 mod audio;
+mod audio_file;
+mod cli;
+mod clipboard;
+mod config;
+mod control;
+mod dictation;
+mod focus;
+mod history;
+mod hyprland;
+mod inject;
+mod profiles;
+mod resample;
+mod rules;
+mod settings;
+mod transcript;
+mod undo;
+mod vad;
+mod virtual_keyboard;
+mod voice_commands;
 mod whisper;
 
 use audio::AudioRecorder;
-use serde::{Deserialize, Serialize};
-use std::io::{Read, Write};
-use std::os::unix::net::{UnixListener, UnixStream};
-use std::path::PathBuf;
-use std::process::Command;
+use dictation::DictationSession;
+use focus::{FocusPolicy, FocusTracker};
+use hyprland::HyprlandError;
+use inject::{ClipboardPaste, InjectorChain};
+use profiles::{Profile, Replacements, TextRules};
+use rules::Rules;
+pub use settings::Settings;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::Duration;
+use std::time::Instant;
 use tauri::{
     menu::{Menu, MenuItem},
     tray::TrayIconBuilder,
     AppHandle, Emitter, Manager, State,
 };
-use whisper::{get_available_models, get_models_directory, ModelInfo, SharedWhisperEngine};
-
-// Socket path for single-instance toggle
-fn get_socket_path() -> PathBuf {
-    let runtime_dir = std::env::var("XDG_RUNTIME_DIR")
-        .unwrap_or_else(|_| "/tmp".to_string());
-    PathBuf::from(runtime_dir).join("hyprwhisper.sock")
-}
+use tauri_plugin_notification::NotificationExt;
+use transcript::Transcript;
+use voice_commands::VoiceCommands;
+use whisper::{
+    get_available_models, get_models_directory, ModelInfo, SharedWhisperEngine,
+    StreamingTranscriber, StreamingUpdate,
+};
 
-// Global flag for stop signal
-static STOP_SIGNAL: AtomicBool = AtomicBool::new(false);
+// Set once dictation starts finishing so a second stop is a no-op
+static FINISHING: AtomicBool = AtomicBool::new(false);
 
-/// Check if another instance is running and signal it to stop
+/// Check if another instance is running and tell it to stop (launch-to-toggle)
 /// Returns true if we should exit (signal was sent to existing instance)
 fn check_and_signal_existing_instance() -> bool {
-    let socket_path = get_socket_path();
-    
-    // Try to connect to existing socket
-    if let Ok(mut stream) = UnixStream::connect(&socket_path) {
-        // Send stop signal
-        let _ = stream.write_all(b"STOP");
-        let _ = stream.flush();
-        println!("Sent stop signal to existing instance");
-        true // Exit this instance
-    } else {
-        false // No existing instance, continue
+    match control::send(&control::Request::Stop) {
+        Ok(response) => {
+            if let Some(error) = response.error {
+                eprintln!("Existing instance: {}", error);
+            }
+            println!("Sent stop signal to existing instance");
+            true // Exit this instance
+        }
+        Err(_) => false, // No existing instance, continue
     }
 }
 
-/// Start listening for stop signals from new instances
-fn start_socket_listener(app_handle: AppHandle) {
-    let socket_path = get_socket_path();
-    
-    // Remove existing socket file
-    let _ = std::fs::remove_file(&socket_path);
-    
-    // Create new socket
-    let listener = match UnixListener::bind(&socket_path) {
-        Ok(l) => l,
-        Err(e) => {
-            eprintln!("Failed to create socket: {}", e);
-            return;
-        }
-    };
-    
-    // Set non-blocking so we can check the stop flag
-    listener.set_nonblocking(true).ok();
-    
-    thread::spawn(move || {
-        loop {
-            match listener.accept() {
-                Ok((mut stream, _)) => {
-                    let mut buf = [0u8; 4];
-                    if stream.read(&mut buf).is_ok() && &buf == b"STOP" {
-                        println!("Received stop signal from new instance");
-                        STOP_SIGNAL.store(true, Ordering::SeqCst);
-                        // Emit event to frontend
-                        app_handle.emit("toggle-stop", ()).ok();
-                    }
-                }
-                Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
-                    // No connection, sleep briefly
-                    thread::sleep(Duration::from_millis(50));
-                }
-                Err(e) => {
-                    eprintln!("Socket error: {}", e);
-                    break;
+/// Handle one request from the control socket
+fn handle_control_request(app: &AppHandle, request: control::Request) -> control::Response {
+    use control::{Request, Response};
+
+    let state = app.state::<AppState>();
+    let recording = state
+        .recorder
+        .lock()
+        .map(|r| r.is_recording())
+        .unwrap_or(false);
+
+    match request {
+        Request::Start => start_dictation_internal(app).into(),
+        Request::Stop | Request::Toggle if recording => {
+            // Typing the tail can take a while; reply right away
+            let app = app.clone();
+            thread::spawn(move || {
+                if let Err(e) = finish_dictation_internal(&app) {
+                    eprintln!("Failed to finish dictation: {}", e);
                 }
-            }
-            
-            // Check if app is exiting
-            if STOP_SIGNAL.load(Ordering::SeqCst) {
-                // Give time for the event to be processed
-                thread::sleep(Duration::from_millis(500));
-                break;
-            }
+            });
+            Response::ok()
         }
-        
-        // Cleanup socket
-        let _ = std::fs::remove_file(get_socket_path());
-    });
+        Request::Stop => Response::error("Not recording"),
+        Request::Toggle => start_dictation_internal(app).into(),
+        Request::Cancel => {
+            cancel_recording_internal(app);
+            Response::ok()
+        }
+        Request::Status => Response::status(current_status(&state)),
+        Request::SetLanguage { language } => set_language(&state, language).into(),
+        Request::LoadModel { model } => load_model_internal(&state, model).into(),
+        // Backspaces would land in the middle of the text being dictated
+        Request::UndoLast if recording => Response::error("Finish dictating first"),
+        Request::UndoLast => undo_last(&state).into(),
+    }
+}
+
+fn current_status(state: &AppState) -> control::Status {
+    let settings = state.settings.lock().map(|s| s.clone()).unwrap_or_default();
+    let partial = state
+        .session
+        .lock()
+        .ok()
+        .and_then(|session| session.as_ref().map(|s| s.partial()))
+        .unwrap_or_default();
+
+    let (recording, input, overruns) = state
+        .recorder
+        .lock()
+        .map(|r| (r.is_recording(), r.capture_format(), r.overruns()))
+        .unwrap_or((false, None, 0));
+
+    control::Status {
+        recording,
+        model: settings.model_filename,
+        model_loaded: state.whisper.lock().map(|w| w.is_loaded()).unwrap_or(false),
+        language: settings.language,
+        committed: partial.committed,
+        tentative: partial.tentative,
+        input,
+        overruns,
+    }
+}
+
+fn undo_last(state: &AppState) -> Result<(), String> {
+    let settings = state.settings.lock().map_err(|e| e.to_string())?.clone();
+    let count = undo::undo_last(&settings)?;
+    println!("Undid the last dictation ({} characters)", count);
+    Ok(())
+}
+
+fn set_language(state: &AppState, language: String) -> Result<(), String> {
+    update_saved_settings(state, |saved| saved.language = language)
+}
+
+/// Change settings.json and refresh the effective settings from it. Only the
+/// saved layer is written, so config.toml keys never leak into settings.json.
+fn update_saved_settings(
+    state: &AppState,
+    change: impl FnOnce(&mut Settings),
+) -> Result<(), String> {
+    let mut saved = state.saved_settings.lock().map_err(|e| e.to_string())?;
+    change(&mut saved);
+    settings::save(&saved)?;
+    // Keys pinned in config.toml still win
+    *state.settings.lock().map_err(|e| e.to_string())? = config::overlay(saved.clone())?;
+    Ok(())
 }
 
 // Application state
 pub struct AppState {
     pub recorder: Arc<Mutex<AudioRecorder>>,
     pub whisper: SharedWhisperEngine,
+    pub streamer: Arc<Mutex<Option<StreamingTranscriber>>>,
+    pub session: Arc<Mutex<Option<DictationSession>>>,
+    /// settings.json overlaid with config.toml; what the app runs on
     pub settings: Arc<Mutex<Settings>>,
-    pub previous_window: Arc<Mutex<Option<String>>>,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Settings {
-    pub model_filename: String,
-    pub language: String,
-    pub hotkey: String,
-    pub auto_paste: bool,
-    pub show_notification: bool,
-}
-
-impl Default for Settings {
-    fn default() -> Self {
-        Self {
-            model_filename: "ggml-base.bin".to_string(),
-            language: "auto".to_string(),
-            hotkey: "Ctrl+Shift+.".to_string(),
-            auto_paste: true,
-            show_notification: true,
+    /// settings.json alone; what changes are saved on top of
+    pub saved_settings: Arc<Mutex<Settings>>,
+    /// Window dictated text goes to, kept up to date from Hyprland events
+    pub focus: Arc<Mutex<FocusTracker>>,
+    /// The target window as it was when dictation started
+    pub target_window: Arc<Mutex<Option<hyprland::Window>>>,
+    /// Every window this dictation's text was typed into, for undo
+    pub typed_windows: Arc<Mutex<Vec<String>>>,
+    /// Profile matched on the target window when dictation started
+    pub profile: Arc<Mutex<Option<Profile>>>,
+    pub text_rules: Arc<Mutex<TextRules>>,
+    /// The user's fixes for mis-hearings
+    pub corrections: Arc<Mutex<Replacements>>,
+    /// Spoken commands for the dictation language
+    pub voice_commands: Arc<Mutex<VoiceCommands>>,
+    /// The user's text rules, with templates filled in when dictation started
+    pub rules: Arc<Mutex<Rules>>,
+}
+
+// ===== Helper Functions =====
+
+/// Settings with the active profile's overrides applied
+fn effective_settings(state: &AppState) -> Result<Settings, String> {
+    let mut settings = state.settings.lock().map_err(|e| e.to_string())?.clone();
+    if let Some(profile) = state.profile.lock().map_err(|e| e.to_string())?.as_ref() {
+        profile.apply(&mut settings);
+    }
+    Ok(settings)
+}
+
+/// Pick the profile for the target window and load its model if it has one
+fn activate_profile(state: &AppState) -> Result<(), String> {
+    let target = state.focus.lock().map_err(|e| e.to_string())?.target();
+    let window = target.and_then(|address| {
+        hyprland::Client::from_env()
+            .and_then(|client| client.window(&address))
+            .unwrap_or_else(|e| {
+                eprintln!("Failed to look up target window: {}", e);
+                None
+            })
+    });
+
+    let profiles = state
+        .settings
+        .lock()
+        .map_err(|e| e.to_string())?
+        .profiles
+        .clone();
+    let mut profile = window
+        .as_ref()
+        .and_then(|window| profiles::select(&profiles, window))
+        .cloned();
+
+    if let (Some(profile), Some(window)) = (&profile, &window) {
+        println!(
+            "Using profile '{}' for {} ({})",
+            profile.name, window.class, window.title
+        );
+    }
+
+    // A profile's model that isn't downloaded shouldn't stop dictation
+    if let Some(profile) = profile.as_mut() {
+        if let Some(model) = &profile.model {
+            if !whisper::model_path(model).is_ok_and(|path| path.is_file()) {
+                eprintln!(
+                    "Profile '{}': model '{}' not found, keeping the current model",
+                    profile.name, model
+                );
+                profile.model = None;
+            }
         }
     }
+
+    if let Some(model) = profile.as_ref().and_then(|p| p.model.as_ref()) {
+        let model_path = whisper::model_path(model)?;
+        let mut whisper = state.whisper.lock().map_err(|e| e.to_string())?;
+        if whisper.get_model_path() != Some(&model_path) {
+            whisper.load_model(model_path)?;
+        }
+    }
+
+    *state.text_rules.lock().map_err(|e| e.to_string())? =
+        profile.as_ref().map(TextRules::new).unwrap_or_default();
+    *state.profile.lock().map_err(|e| e.to_string())? = profile;
+    *state.target_window.lock().map_err(|e| e.to_string())? = window;
+    state
+        .typed_windows
+        .lock()
+        .map_err(|e| e.to_string())?
+        .clear();
+
+    let settings = effective_settings(state)?;
+    *state.corrections.lock().map_err(|e| e.to_string())? =
+        Replacements::new(&settings.corrections, "Corrections");
+    *state.voice_commands.lock().map_err(|e| e.to_string())? = if settings.voice_commands {
+        VoiceCommands::new(&settings.language, &settings.custom_voice_commands)
+    } else {
+        VoiceCommands::disabled()
+    };
+    *state.rules.lock().map_err(|e| e.to_string())? =
+        Rules::new(&settings.rules, chrono::Local::now());
+    Ok(())
 }
 
-// ===== Helper Functions =====
+/// What the target should show for the session's committed text
+fn render_output(state: &AppState, session: &DictationSession) -> Result<String, String> {
+    render_text(state, &session.committed())
+}
 
-/// Get the currently focused window address using hyprctl
+/// Post-process a transcript: mis-hearings corrected, spoken commands
+/// carried out, the profile's text rules and finally the user's rules
+fn render_text(state: &AppState, transcript: &str) -> Result<String, String> {
+    let text = state
+        .corrections
+        .lock()
+        .map_err(|e| e.to_string())?
+        .apply(transcript);
+    let text = state
+        .voice_commands
+        .lock()
+        .map_err(|e| e.to_string())?
+        .render(&text);
+    let text = state
+        .text_rules
+        .lock()
+        .map_err(|e| e.to_string())?
+        .apply(&text);
+    Ok(state.rules.lock().map_err(|e| e.to_string())?.apply(&text))
+}
+
+/// Show a desktop notification if the user hasn't turned them off
+fn notify(app: &AppHandle, settings: &Settings, title: &str, body: &str) {
+    if !settings.show_notification {
+        return;
+    }
+    if let Err(e) = app.notification().builder().title(title).body(body).show() {
+        eprintln!("Failed to show notification: {}", e);
+    }
+}
+
+/// Get the currently focused window address over Hyprland IPC
 fn get_active_window_address() -> Option<String> {
-    let output = Command::new("hyprctl")
-        .args(["activewindow", "-j"])
-        .output()
-        .ok()?;
-    
-    let json: serde_json::Value = serde_json::from_slice(&output.stdout).ok()?;
-    json.get("address")?.as_str().map(|s| s.to_string())
+    let window = hyprland::Client::from_env().and_then(|client| client.active_window());
+    match window {
+        Ok(window) => window.map(|w| w.address),
+        Err(HyprlandError::NotRunning) => None,
+        Err(e) => {
+            eprintln!("Failed to get active window: {}", e);
+            None
+        }
+    }
 }
 
 // ===== Tauri Commands =====
 
-#[tauri::command]
-async fn start_recording(state: State<'_, AppState>) -> Result<(), String> {
-    let mut recorder = state.recorder.lock().map_err(|e| e.to_string())?;
-    recorder.start_recording()
+/// Watch a running recording: report a lost input device, forward voice
+/// activity events and end the session once trailing silence exceeds
+/// `auto_stop_silence_ms`
+fn spawn_recording_monitor(
+    app: AppHandle,
+    recorder: Arc<Mutex<AudioRecorder>>,
+    settings: Settings,
+) {
+    let auto_stop = (settings.auto_stop_silence_ms > 0)
+        .then(|| Duration::from_millis(settings.auto_stop_silence_ms));
+
+    // Speech-end fires only after the VAD's hangover of silence
+    let hangover = vad::VadConfig::default().hangover();
+
+    thread::spawn(move || {
+        // Armed by the first speech-end, so a slow start doesn't end the recording
+        let mut silent_since: Option<Instant> = None;
+        let mut finish = false;
+
+        loop {
+            thread::sleep(Duration::from_millis(50));
+
+            let (events, stream_error) = {
+                let Ok(recorder) = recorder.lock() else { break };
+                if !recorder.is_recording() {
+                    break;
+                }
+                (recorder.poll_vad_events(), recorder.take_stream_error())
+            };
+
+            if let Some(error) = stream_error {
+                eprintln!("{}", error);
+                notify(&app, &settings, "Recording interrupted", &error);
+                app.emit("recording-error", &error).ok();
+                // Type whatever was captured before the device went away
+                finish = true;
+                break;
+            }
+
+            if !settings.vad_enabled {
+                continue;
+            }
+
+            for event in events {
+                match event {
+                    vad::VadEvent::SpeechStart { .. } => {
+                        silent_since = None;
+                        app.emit("speech-start", event).ok();
+                    }
+                    vad::VadEvent::SpeechEnd { .. } => {
+                        let now = Instant::now();
+                        silent_since = Some(now.checked_sub(hangover).unwrap_or(now));
+                        app.emit("speech-end", event).ok();
+                    }
+                }
+            }
+
+            if let (Some(limit), Some(since)) = (auto_stop, silent_since) {
+                if since.elapsed() >= limit {
+                    println!("Trailing silence exceeded {:?}, stopping dictation", limit);
+                    app.emit("auto-stop", ()).ok();
+                    finish = true;
+                    break;
+                }
+            }
+        }
+
+        if finish {
+            if let Err(e) = finish_dictation_internal(&app) {
+                eprintln!("Failed to finish dictation: {}", e);
+            }
+        }
+    });
 }
 
-#[tauri::command]
-async fn stop_recording(state: State<'_, AppState>) -> Result<String, String> {
+/// Start capturing audio and reset the streaming transcriber
+fn begin_recording(app: &AppHandle, state: &AppState) -> Result<(), String> {
+    let settings = state.settings.lock().map_err(|e| e.to_string())?.clone();
+    let prompt = effective_settings(state)?.whisper_prompt();
+
+    // `transcribe_step` locks the streamer before the recorder, so the
+    // recorder is released before the streamer is touched
+    let (device, sample_rate) = {
+        let mut recorder = state.recorder.lock().map_err(|e| e.to_string())?;
+        let device = recorder.start_recording(
+            settings.input_device.as_deref(),
+            settings.input_device_fallback,
+        )?;
+        (device, recorder.sample_rate())
+    };
+
+    if let Some(reason) = &device.fallback_reason {
+        eprintln!("{}; using '{}'", reason, device.name);
+        notify(
+            app,
+            &settings,
+            "Input device unavailable",
+            &format!("{}. Recording from '{}' instead.", reason, device.name),
+        );
+    }
+
+    *state.streamer.lock().map_err(|e| e.to_string())? =
+        Some(StreamingTranscriber::new(sample_rate, prompt));
+
+    spawn_recording_monitor(app.clone(), Arc::clone(&state.recorder), settings);
+
+    Ok(())
+}
+
+/// Stop capturing and return the final transcript of the whole session
+fn stop_and_transcribe(state: &AppState) -> Result<Transcript, String> {
     let samples = {
         let mut recorder = state.recorder.lock().map_err(|e| e.to_string())?;
         recorder.stop_recording()?
     };
 
+    let streamer = state.streamer.lock().map_err(|e| e.to_string())?.take();
+
     if samples.is_empty() {
-        return Ok(String::new());
+        return Ok(Transcript::default());
     }
 
-    // Get language setting
-    let language = {
-        let settings = state.settings.lock().map_err(|e| e.to_string())?;
-        if settings.language == "auto" {
-            None
+    let settings = effective_settings(state)?;
+    let language = settings.whisper_language();
+
+    // Transcribe
+    let whisper = state.whisper.lock().map_err(|e| e.to_string())?;
+    if !whisper.is_loaded() {
+        return Err("Model not loaded. Please load a model first.".to_string());
+    }
+
+    // Reuse what streaming already committed and only transcribe the tail
+    match streamer {
+        Some(mut streamer) => {
+            let offset = streamer.consumed_resampled().min(samples.len());
+            streamer.finish(&whisper, &samples[offset..], language.as_deref())
+        }
+        None => {
+            whisper.transcribe_detailed(&samples, language.as_deref(), &settings.whisper_prompt())
+        }
+    }
+}
+
+/// Pull new audio into the streaming transcriber and re-transcribe its window.
+/// Returns `None` once recording has stopped.
+fn transcribe_step(state: &AppState) -> Result<Option<StreamingUpdate>, String> {
+    let mut streamer = state.streamer.lock().map_err(|e| e.to_string())?;
+    let Some(streamer) = streamer.as_mut() else {
+        return Ok(None);
+    };
+
+    let new_samples = {
+        let recorder = state.recorder.lock().map_err(|e| e.to_string())?;
+        if recorder.get_sample_count() > streamer.consumed() {
+            recorder.get_samples_from(streamer.consumed())
         } else {
-            Some(settings.language.clone())
+            Vec::new()
         }
     };
+    streamer.push(&new_samples);
+
+    let language = effective_settings(state)?.whisper_language();
 
     // Transcribe
     let whisper = state.whisper.lock().map_err(|e| e.to_string())?;
     if !whisper.is_loaded() {
-        return Err("Model not loaded. Please load a model first.".to_string());
+        return Err("Model not loaded".to_string());
+    }
+
+    streamer.step(&whisper, language.as_deref()).map(Some)
+}
+
+/// Bring the target in line with the committed text and tell the frontend
+fn type_pending(
+    app: &AppHandle,
+    state: &AppState,
+    session: &mut DictationSession,
+) -> Result<(), String> {
+    let output = render_output(state, session)?;
+    let Some(edit) = session.pending_edit(&output) else {
+        return Ok(());
+    };
+
+    // Held back while the target is gone under the pause policy
+    let policy = state
+        .settings
+        .lock()
+        .map_err(|e| e.to_string())?
+        .focus_policy;
+    if !state
+        .focus
+        .lock()
+        .map_err(|e| e.to_string())?
+        .may_type(policy)
+    {
+        return Ok(());
+    }
+
+    edit_text(state, edit.backspaces, &edit.text)?;
+    session.mark_typed(&output);
+
+    app.emit(
+        "committed-text",
+        serde_json::json!({
+            "text": edit.text,
+            "backspaces": edit.backspaces,
+            "committed": output,
+        }),
+    )
+    .ok();
+
+    Ok(())
+}
+
+/// Put the final transcript on the clipboard instead of typing it, optionally
+/// pasting it and then restoring what was on the clipboard before
+fn copy_transcript(state: &AppState, text: &str) -> Result<(), String> {
+    if text.is_empty() {
+        return Ok(());
+    }
+
+    let settings = state.settings.lock().map_err(|e| e.to_string())?.clone();
+
+    let previous = if settings.paste_from_clipboard && settings.restore_clipboard {
+        clipboard::snapshot(false)
+            .map_err(|e| eprintln!("Failed to save clipboard: {}", e))
+            .ok()
+    } else {
+        None
+    };
+
+    clipboard::copy(text, false)?;
+    if settings.copy_to_primary {
+        clipboard::copy(text, true)?;
+    }
+
+    if settings.paste_from_clipboard {
+        focus_target_window(state)?;
+        thread::sleep(Duration::from_millis(50));
+        ClipboardPaste::new().send_paste()?;
+
+        if let Some(previous) = previous {
+            // The target reads the selection asynchronously after the paste
+            thread::sleep(Duration::from_millis(300));
+            previous.restore()?;
+        }
+    }
+
+    println!("Copied {} chars to the clipboard", text.chars().count());
+    Ok(())
+}
+
+/// Live dictation loop: every 400ms transcribe new audio, commit the words
+/// that consecutive hypotheses agree on, and type them into the target
+fn spawn_dictation_loop(app: AppHandle) {
+    thread::spawn(move || {
+        let state = app.state::<AppState>();
+
+        loop {
+            thread::sleep(Duration::from_millis(400));
+
+            let update = match transcribe_step(&state) {
+                Ok(Some(update)) => update,
+                Ok(None) => break,
+                Err(e) => {
+                    eprintln!("Live transcription failed: {}", e);
+                    continue;
+                }
+            };
+
+            let Ok(mut session) = state.session.lock() else {
+                break;
+            };
+            let Some(session) = session.as_mut() else {
+                break;
+            };
+
+            session.push_hypothesis(&update.text());
+            app.emit("partial-transcript", session.partial()).ok();
+
+            // In clipboard mode nothing is typed; the transcript is copied at the end
+            let auto_paste = state.settings.lock().map(|s| s.auto_paste).unwrap_or(true);
+            if !auto_paste {
+                continue;
+            }
+
+            if let Err(e) = type_pending(&app, &state, session) {
+                // Left pending, so the next tick retries
+                eprintln!("Failed to type: {}", e);
+            }
+        }
+    });
+}
+
+#[tauri::command]
+async fn start_recording(app: AppHandle, state: State<'_, AppState>) -> Result<(), String> {
+    ensure_not_recording(&state)?;
+    activate_profile(&state)?;
+    begin_recording(&app, &state)
+}
+
+/// Fail before a new start touches the profile, rules or model of the
+/// dictation that is still running
+fn ensure_not_recording(state: &AppState) -> Result<(), String> {
+    if state
+        .recorder
+        .lock()
+        .map_err(|e| e.to_string())?
+        .is_recording()
+    {
+        return Err("Already recording".to_string());
+    }
+    Ok(())
+}
+
+/// Start recording and run live transcription and typing in the backend
+#[tauri::command]
+async fn start_dictation(app: AppHandle) -> Result<(), String> {
+    start_dictation_internal(&app)
+}
+
+fn start_dictation_internal(app: &AppHandle) -> Result<(), String> {
+    let state = app.state::<AppState>();
+
+    ensure_not_recording(&state)?;
+    activate_profile(&state)?;
+
+    if !state.whisper.lock().map_err(|e| e.to_string())?.is_loaded() {
+        return Err("Model not loaded".to_string());
+    }
+
+    let agreement = state
+        .settings
+        .lock()
+        .map_err(|e| e.to_string())?
+        .local_agreement;
+
+    begin_recording(app, &state)?;
+    *state.session.lock().map_err(|e| e.to_string())? = Some(DictationSession::new(agreement));
+
+    spawn_dictation_loop(app.clone());
+
+    if let Some(window) = app.get_webview_window("main") {
+        window.show().ok();
     }
+    app.emit("dictation-started", ()).ok();
+
+    Ok(())
+}
 
-    whisper.transcribe(&samples, language.as_deref())
+#[tauri::command]
+async fn stop_recording(state: State<'_, AppState>) -> Result<String, String> {
+    let text = stop_and_transcribe(&state)?.text();
+    render_text(&state, &text)
 }
 
 /// Stop recording without transcribing - just cleanup
 #[tauri::command]
 fn stop_recording_silent(state: State<'_, AppState>) -> Result<(), String> {
-    let mut recorder = state.recorder.lock().map_err(|e| e.to_string())?;
-    let _ = recorder.stop_recording();
+    {
+        let mut recorder = state.recorder.lock().map_err(|e| e.to_string())?;
+        let _ = recorder.stop_recording();
+    }
+    // Ends the dictation loop too
+    *state.streamer.lock().map_err(|e| e.to_string())? = None;
+    *state.session.lock().map_err(|e| e.to_string())? = None;
     Ok(())
 }
 
@@ -199,40 +698,13 @@ async fn is_recording(state: State<'_, AppState>) -> Result<bool, String> {
     Ok(recorder.is_recording())
 }
 
-/// Transcribe current audio buffer without stopping recording (for real-time preview)
+/// Transcribe new audio without stopping recording (for real-time preview).
+/// Only audio recorded since the last call is pulled from the recorder.
 #[tauri::command]
 async fn transcribe_current(state: State<'_, AppState>) -> Result<String, String> {
-    let samples = {
-        let recorder = state.recorder.lock().map_err(|e| e.to_string())?;
-        recorder.get_current_samples()
-    };
-
-    if samples.is_empty() {
-        return Ok(String::new());
-    }
-
-    // Need at least 0.5 seconds of audio (8000 samples at 16kHz)
-    if samples.len() < 8000 {
-        return Ok(String::new());
-    }
-
-    // Get language setting
-    let language = {
-        let settings = state.settings.lock().map_err(|e| e.to_string())?;
-        if settings.language == "auto" {
-            None
-        } else {
-            Some(settings.language.clone())
-        }
-    };
-
-    // Transcribe
-    let whisper = state.whisper.lock().map_err(|e| e.to_string())?;
-    if !whisper.is_loaded() {
-        return Err("Model not loaded".to_string());
-    }
-
-    whisper.transcribe_chunk(&samples, language.as_deref())
+    Ok(transcribe_step(&state)?
+        .map(|update| update.text())
+        .unwrap_or_default())
 }
 
 /// Get sample count for tracking transcription progress
@@ -244,15 +716,28 @@ async fn get_sample_count(state: State<'_, AppState>) -> Result<usize, String> {
 
 #[tauri::command]
 async fn load_model(state: State<'_, AppState>, filename: String) -> Result<(), String> {
-    let model_path = get_models_directory().join(&filename);
-    
+    load_model_internal(&state, filename)
+}
+
+fn load_model_internal(state: &AppState, filename: String) -> Result<(), String> {
+    let model_path = whisper::model_path(&filename)?;
+
     let mut whisper = state.whisper.lock().map_err(|e| e.to_string())?;
     whisper.load_model(model_path)?;
-    
+
+    drop(whisper);
+
     // Update settings
-    let mut settings = state.settings.lock().map_err(|e| e.to_string())?;
-    settings.model_filename = filename;
-    
+    let saved = state
+        .saved_settings
+        .lock()
+        .map_err(|e| e.to_string())?
+        .model_filename
+        .clone();
+    if saved != filename {
+        update_saved_settings(state, |saved| saved.model_filename = filename)?;
+    }
+
     Ok(())
 }
 
@@ -274,67 +759,12 @@ fn get_models_dir() -> String {
 
 #[tauri::command]
 fn get_downloaded_models() -> Vec<String> {
-    let models_dir = get_models_directory();
-    std::fs::read_dir(&models_dir)
-        .map(|entries| {
-            entries
-                .filter_map(|e| e.ok())
-                .filter_map(|e| {
-                    let name = e.file_name().to_string_lossy().to_string();
-                    if name.ends_with(".bin") {
-                        Some(name)
-                    } else {
-                        None
-                    }
-                })
-                .collect()
-        })
-        .unwrap_or_default()
+    whisper::get_downloaded_models()
 }
 
 #[tauri::command]
-async fn download_model(
-    app: AppHandle,
-    model: ModelInfo,
-) -> Result<(), String> {
-    use futures_util::StreamExt;
-
-    let models_dir = get_models_directory();
-    let model_path = models_dir.join(&model.filename);
-
-    if model_path.exists() {
-        return Ok(());
-    }
-
-    // Create a temp file for downloading
-    let temp_path = model_path.with_extension("bin.part");
-
-    let client = reqwest::Client::new();
-    let response = client
-        .get(&model.url)
-        .send()
-        .await
-        .map_err(|e| format!("Download failed: {}", e))?;
-
-    let total_size = response.content_length().unwrap_or(0);
-    let mut downloaded: u64 = 0;
-
-    let mut file = tokio::fs::File::create(&temp_path)
-        .await
-        .map_err(|e| format!("Failed to create file: {}", e))?;
-
-    let mut stream = response.bytes_stream();
-
-    while let Some(chunk) = stream.next().await {
-        let chunk = chunk.map_err(|e| format!("Download error: {}", e))?;
-        
-        use tokio::io::AsyncWriteExt;
-        file.write_all(&chunk)
-            .await
-            .map_err(|e| format!("Write error: {}", e))?;
-
-        downloaded += chunk.len() as u64;
-
+async fn download_model(app: AppHandle, model: ModelInfo) -> Result<(), String> {
+    whisper::download_model(&model, |downloaded, total_size| {
         // Emit progress event
         let progress = if total_size > 0 {
             (downloaded as f64 / total_size as f64 * 100.0) as u32
@@ -342,22 +772,18 @@ async fn download_model(
             0
         };
 
-        app.emit("download-progress", serde_json::json!({
-            "filename": model.filename,
-            "progress": progress,
-            "downloaded": downloaded,
-            "total": total_size,
-        })).ok();
-    }
-
-    file.sync_all()
-        .await
-        .map_err(|e| format!("Sync error: {}", e))?;
-
-    // Rename temp file to final name
-    tokio::fs::rename(&temp_path, &model_path)
-        .await
-        .map_err(|e| format!("Rename error: {}", e))?;
+        app.emit(
+            "download-progress",
+            serde_json::json!({
+                "filename": model.filename,
+                "progress": progress,
+                "downloaded": downloaded,
+                "total": total_size,
+            }),
+        )
+        .ok();
+    })
+    .await?;
 
     app.emit("download-complete", &model.filename).ok();
 
@@ -366,12 +792,7 @@ async fn download_model(
 
 #[tauri::command]
 async fn delete_model(filename: String) -> Result<(), String> {
-    let model_path = get_models_directory().join(&filename);
-    if model_path.exists() {
-        std::fs::remove_file(&model_path)
-            .map_err(|e| format!("Failed to delete model: {}", e))?;
-    }
-    Ok(())
+    whisper::delete_model(&filename)
 }
 
 #[tauri::command]
@@ -382,9 +803,67 @@ fn get_settings(state: State<'_, AppState>) -> Result<Settings, String> {
 
 #[tauri::command]
 fn save_settings(state: State<'_, AppState>, settings: Settings) -> Result<(), String> {
-    let mut current = state.settings.lock().map_err(|e| e.to_string())?;
-    *current = settings;
-    Ok(())
+    let before = state.settings.lock().map_err(|e| e.to_string())?.clone();
+    let saved = state
+        .saved_settings
+        .lock()
+        .map_err(|e| e.to_string())?
+        .clone();
+    let changed = config::apply_changes(&saved, &before, &settings)?;
+    update_saved_settings(&state, |saved| *saved = changed)
+}
+
+/// Teach Whisper a term, optionally with what it mis-heard instead
+/// (e.g. `term: "kubectl", heard: "cube control"`)
+#[tauri::command]
+fn add_vocabulary_term(
+    state: State<'_, AppState>,
+    term: String,
+    heard: Option<String>,
+) -> Result<(), String> {
+    update_saved_settings(&state, |saved| {
+        saved.add_vocabulary_term(&term, heard.as_deref());
+    })
+}
+
+/// Swap in settings reloaded from config.toml, reloading the model if it changed
+fn apply_reloaded_settings(app: &AppHandle, reloaded: Result<Settings, String>) {
+    let state = app.state::<AppState>();
+
+    let settings = match reloaded {
+        Ok(settings) => settings,
+        Err(e) => {
+            eprintln!("Keeping current settings: {}", e);
+            if let Ok(current) = state.settings.lock() {
+                notify(app, &current, "HyprWhisper config error", &e);
+            }
+            return;
+        }
+    };
+
+    let previous = match state.settings.lock() {
+        Ok(mut current) => std::mem::replace(&mut *current, settings.clone()),
+        Err(e) => {
+            eprintln!("Failed to apply settings: {}", e);
+            return;
+        }
+    };
+
+    if previous.model_filename != settings.model_filename {
+        let model_path = get_models_directory().join(&settings.model_filename);
+        let result = state
+            .whisper
+            .lock()
+            .map_err(|e| e.to_string())
+            .and_then(|mut whisper| whisper.load_model(model_path));
+
+        if let Err(e) = result {
+            eprintln!("Failed to load model from config: {}", e);
+            notify(app, &settings, "HyprWhisper config error", &e);
+        }
+    }
+
+    app.emit("settings-changed", &settings).ok();
 }
 
 #[tauri::command]
@@ -392,51 +871,91 @@ fn get_input_devices() -> Vec<String> {
     audio::get_input_devices()
 }
 
-/// Type text directly to the previously focused window using wtype
-/// This is used for real-time dictation - types incrementally as words become stable
+/// Type text directly to the previously focused window
 #[tauri::command]
 fn wtype_text(state: State<'_, AppState>, text: String) -> Result<(), String> {
-    if text.is_empty() {
-        return Ok(());
+    type_text(&state, &text)
+}
+
+/// Feed Hyprland focus events to the focus tracker for the app's lifetime
+fn track_focus(app: AppHandle) {
+    let result = hyprland::listen(move |event| {
+        let state = app.state::<AppState>();
+        let Ok(policy) = state.settings.lock().map(|s| s.focus_policy) else {
+            return;
+        };
+
+        // Our own overlay taking focus must not become the target
+        if policy == FocusPolicy::Follow && matches!(event, hyprland::Event::ActiveWindow(Some(_)))
+        {
+            let focused = hyprland::Client::from_env().and_then(|client| client.active_window());
+            if let Ok(Some(window)) = focused {
+                if window.pid == std::process::id() as i64 {
+                    return;
+                }
+            }
+        }
+
+        if let Ok(mut focus) = state.focus.lock() {
+            focus.handle_event(&event, policy);
+        };
+    });
+
+    match result {
+        Ok(()) => {}
+        Err(HyprlandError::NotRunning) => {}
+        Err(e) => eprintln!("Not tracking focus changes: {}", e),
     }
-    
-    // Get the previous window address
-    let prev_window = state.previous_window.lock().unwrap().clone();
-    
-    // Focus previous window if we have one
-    if let Some(addr) = prev_window {
-        let focus_result = Command::new("hyprctl")
-            .args(["dispatch", "focuswindow", &format!("address:{}", addr)])
-            .output();
-        
+}
+
+/// Focus the target window, if we know it
+fn focus_target_window(state: &AppState) -> Result<(), String> {
+    let target = state.focus.lock().map_err(|e| e.to_string())?.target();
+
+    if let Some(addr) = target {
+        let focus_result =
+            hyprland::Client::from_env().and_then(|client| client.focus_window(&addr));
+
         if let Err(e) = focus_result {
             eprintln!("Failed to focus window: {}", e);
         }
-        
+
         // Brief delay for focus to complete
         thread::sleep(Duration::from_millis(30));
     }
-    
-    // Type text directly using wtype
-    let type_result = Command::new("wtype")
-        .arg("--")
-        .arg(&text)
-        .output();
-    
-    match type_result {
-        Ok(output) => {
-            if !output.status.success() {
-                let stderr = String::from_utf8_lossy(&output.stderr);
-                eprintln!("wtype failed: {}", stderr);
-                return Err(format!("wtype failed: {}", stderr));
-            }
-        }
-        Err(e) => {
-            eprintln!("Failed to run wtype: {}", e);
-            return Err(format!("Failed to run wtype: {}", e));
+    Ok(())
+}
+
+/// Focus the target window (if any) and type `text` into it with
+/// the first configured injection backend that works
+fn type_text(state: &AppState, text: &str) -> Result<(), String> {
+    edit_text(state, 0, text)
+}
+
+/// Focus the target window (if any), delete `backspaces` characters and
+/// type `text` with the first configured injection backend that works
+fn edit_text(state: &AppState, backspaces: usize, text: &str) -> Result<(), String> {
+    if backspaces == 0 && text.is_empty() {
+        return Ok(());
+    }
+
+    focus_target_window(state)?;
+
+    // Where the keys actually go; under `follow` that can change between edits
+    if let Some(address) = get_active_window_address() {
+        let mut typed_windows = state.typed_windows.lock().map_err(|e| e.to_string())?;
+        if !typed_windows.contains(&address) {
+            typed_windows.push(address);
         }
     }
-    
+
+    let injectors = effective_settings(state)?.text_injectors;
+
+    let backend = InjectorChain::from_names(&injectors).edit(backspaces, text)?;
+    if backspaces > 0 {
+        println!("Deleted {} chars with {}", backspaces, backend);
+    }
+    println!("Typed {} chars with {}", text.chars().count(), backend);
     Ok(())
 }
 
@@ -446,59 +965,171 @@ fn exit_app(app: AppHandle) {
     app.exit(0);
 }
 
-/// Called when user finishes dictation - types remaining text and exits
+/// Called when user finishes dictation - transcribes the tail, types whatever
+/// the session has not typed yet and exits
 #[tauri::command]
-fn finish_dictation(app: AppHandle, state: State<'_, AppState>, remaining_text: String) {
-    // Stop recording immediately
-    {
-        let mut recorder = state.recorder.lock().unwrap();
-        let _ = recorder.stop_recording();
+async fn finish_dictation(app: AppHandle) -> Result<(), String> {
+    finish_dictation_internal(&app)
+}
+
+fn finish_dictation_internal(app: &AppHandle) -> Result<(), String> {
+    if FINISHING.swap(true, Ordering::SeqCst) {
+        return Ok(());
     }
-    
+
+    let state = app.state::<AppState>();
+
     // Hide the window immediately
     if let Some(window) = app.get_webview_window("main") {
         let _ = window.hide();
     }
-    
-    // Type remaining text if any
-    if !remaining_text.trim().is_empty() {
-        let text = remaining_text.trim().to_string();
-        
-        // Get the previous window address
-        let prev_window = state.previous_window.lock().unwrap().clone();
-        
-        // Small delay for window to fully hide
-        thread::sleep(Duration::from_millis(50));
-        
-        // Focus previous window if we have one
-        if let Some(addr) = prev_window {
-            let _ = Command::new("hyprctl")
-                .args(["dispatch", "focuswindow", &format!("address:{}", addr)])
-                .output();
-            
+
+    let duration_ms = state
+        .recorder
+        .lock()
+        .map(|r| r.get_sample_count() as u64 * 1000 / r.sample_rate().max(1) as u64)
+        .unwrap_or(0);
+
+    let transcript = stop_and_transcribe(&state);
+
+    // Without a live session (plain start_recording) everything is still untyped
+    let mut session = state
+        .session
+        .lock()
+        .map_err(|e| e.to_string())?
+        .take()
+        .unwrap_or_else(|| DictationSession::new(1));
+
+    match transcript {
+        Ok(transcript) => {
+            session.finish(&transcript.text());
+
+            // Small delay for window to fully hide
             thread::sleep(Duration::from_millis(50));
+
+            let (auto_paste, policy) = state
+                .settings
+                .lock()
+                .map(|s| (s.auto_paste, s.focus_policy))
+                .unwrap_or((true, FocusPolicy::Pin));
+            let target_gone = !state
+                .focus
+                .lock()
+                .map(|f| f.may_type(policy))
+                .unwrap_or(true);
+
+            let delivered = if target_gone {
+                // Don't type into some other window; leave the text on the clipboard
+                if let Ok(settings) = state.settings.lock() {
+                    notify(
+                        app,
+                        &settings,
+                        "HyprWhisper",
+                        "Target window closed, transcript copied to clipboard",
+                    );
+                }
+                render_output(&state, &session).and_then(|output| clipboard::copy(&output, false))
+            } else if auto_paste {
+                type_pending(app, &state, &mut session)
+            } else {
+                render_output(&state, &session).and_then(|output| copy_transcript(&state, &output))
+            };
+
+            let error = delivered.err();
+            if let Some(e) = &error {
+                eprintln!("Failed to deliver final text: {}", e);
+                if let Ok(settings) = state.settings.lock() {
+                    notify(app, &settings, "HyprWhisper", e);
+                }
+            }
+
+            let output = render_output(&state, &session).unwrap_or_else(|_| session.committed());
+            let typed = !output.is_empty() && session.typed() == output;
+            let record = record_history(&state, &output, &transcript, typed, duration_ms, error);
+            if let Err(e) = record {
+                eprintln!("Failed to save history: {}", e);
+            }
         }
-        
-        // Type remaining text
-        let _ = Command::new("wtype")
-            .arg("--")
-            .arg(&text)
-            .output();
-    }
-    
+        Err(e) => eprintln!("Final transcription failed: {}", e),
+    }
+
     // Exit the app
     app.exit(0);
+    Ok(())
+}
+
+/// Save a finished dictation to the history, if it is enabled
+fn record_history(
+    state: &AppState,
+    text: &str,
+    transcript: &Transcript,
+    typed: bool,
+    duration_ms: u64,
+    error: Option<String>,
+) -> Result<(), String> {
+    let settings = effective_settings(state)?;
+    if !settings.history || text.is_empty() {
+        return Ok(());
+    }
+
+    let window_class = state
+        .target_window
+        .lock()
+        .map_err(|e| e.to_string())?
+        .as_ref()
+        .map(|window| window.class.clone());
+    // Undo can only backspace over text that all went to one window
+    let window_address = match state
+        .typed_windows
+        .lock()
+        .map_err(|e| e.to_string())?
+        .as_slice()
+    {
+        [address] => Some(address.clone()),
+        _ => None,
+    };
+
+    let entry = history::Entry {
+        id: 0,
+        timestamp: chrono::Local::now(),
+        window_class,
+        window_address,
+        model: settings.model_filename.clone(),
+        language: settings.language.clone(),
+        duration_ms,
+        text: text.to_string(),
+        // Timings are enough to export captions; token probabilities would
+        // only bloat the file
+        segments: transcript
+            .segments
+            .iter()
+            .map(|segment| transcript::Segment {
+                tokens: Vec::new(),
+                ..segment.clone()
+            })
+            .collect(),
+        error,
+        typed,
+        undone: false,
+    };
+    history::append(entry, settings.history_retention()).map(|_| ())
 }
 
 /// Called on cancel - just cleanup and close
 #[tauri::command]
-fn cancel_recording(app: AppHandle, state: State<'_, AppState>) {
+fn cancel_recording(app: AppHandle) {
+    cancel_recording_internal(&app);
+}
+
+fn cancel_recording_internal(app: &AppHandle) {
+    let state = app.state::<AppState>();
+
     // Stop recording
     {
         let mut recorder = state.recorder.lock().unwrap();
         let _ = recorder.stop_recording();
     }
-    
+
     // Exit the app
     app.exit(0);
 }
@@ -507,7 +1138,9 @@ fn setup_global_shortcut(_app: &AppHandle) -> Result<(), Box<dyn std::error::Err
     // Global shortcuts have issues on Wayland/Hyprland
     // For now, users can use the app window and press Space to record
     // TODO: Implement proper Wayland global shortcut support via portal or hyprland IPC
-    println!("Note: Global shortcuts disabled on Wayland. Use the app window (Space key) to record.");
+    println!(
+        "Note: Global shortcuts disabled on Wayland. Use the app window (Space key) to record."
+    );
     Ok(())
 }
 
@@ -522,22 +1155,20 @@ fn setup_tray(app: &AppHandle) -> Result<(), Box<dyn std::error::Error>> {
         .icon(app.default_window_icon().unwrap().clone())
         .menu(&menu)
         .tooltip("HyprWhisper - Speech to Text")
-        .on_menu_event(|app, event| {
-            match event.id().as_ref() {
-                "show" => {
-                    if let Some(window) = app.get_webview_window("main") {
-                        window.show().ok();
-                        window.set_focus().ok();
-                    }
-                }
-                "settings" => {
-                    app.emit("open-settings", ()).ok();
-                }
-                "quit" => {
-                    app.exit(0);
+        .on_menu_event(|app, event| match event.id().as_ref() {
+            "show" => {
+                if let Some(window) = app.get_webview_window("main") {
+                    window.show().ok();
+                    window.set_focus().ok();
                 }
-                _ => {}
             }
+            "settings" => {
+                app.emit("open-settings", ()).ok();
+            }
+            "quit" => {
+                app.exit(0);
+            }
+            _ => {}
         })
         .build(app)?;
 
@@ -546,35 +1177,54 @@ fn setup_tray(app: &AppHandle) -> Result<(), Box<dyn std::error::Error>> {
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    // Subcommands like `status` or `models list` run without a window
+    if let Some(code) = cli::dispatch() {
+        std::process::exit(code);
+    }
+
     // Toggle mode: check if another instance is running
     if check_and_signal_existing_instance() {
         println!("Signaled existing instance to stop, exiting");
         return;
     }
-    
+
     // Capture the previous window BEFORE we create our window
     let previous_window = get_active_window_address();
     println!("Captured previous window at startup: {:?}", previous_window);
-    
+
+    let (saved_settings, settings, settings_errors) = config::load_saved_and_layered();
+    for error in &settings_errors {
+        eprintln!("Settings error: {}", error);
+    }
+
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_shell::init())
-        .plugin(tauri_plugin_store::Builder::new().build())
         .plugin(tauri_plugin_notification::init())
         .plugin(tauri_plugin_global_shortcut::Builder::new().build())
         .manage(AppState {
             recorder: Arc::new(Mutex::new(AudioRecorder::new())),
             whisper: whisper::create_shared_engine(),
-            settings: Arc::new(Mutex::new(Settings::default())),
-            previous_window: Arc::new(Mutex::new(previous_window)),
+            streamer: Arc::new(Mutex::new(None)),
+            session: Arc::new(Mutex::new(None)),
+            settings: Arc::new(Mutex::new(settings)),
+            saved_settings: Arc::new(Mutex::new(saved_settings)),
+            focus: Arc::new(Mutex::new(FocusTracker::new(previous_window))),
+            target_window: Arc::new(Mutex::new(None)),
+            typed_windows: Arc::new(Mutex::new(Vec::new())),
+            profile: Arc::new(Mutex::new(None)),
+            text_rules: Arc::new(Mutex::new(TextRules::default())),
+            corrections: Arc::new(Mutex::new(Replacements::default())),
+            voice_commands: Arc::new(Mutex::new(VoiceCommands::disabled())),
+            rules: Arc::new(Mutex::new(Rules::default())),
         })
-        .setup(|app| {
+        .setup(move |app| {
             // Set WebView background to transparent on Linux
             #[cfg(target_os = "linux")]
             {
                 if let Some(window) = app.get_webview_window("main") {
                     use webkit2gtk::WebViewExt;
-                    
+
                     let _ = window.with_webview(|webview| {
                         // Get the webkit2gtk WebView and set transparent background
                         let wv = webview.inner();
@@ -583,10 +1233,30 @@ pub fn run() {
                     });
                 }
             }
-            
-            // Start socket listener for toggle mode
-            start_socket_listener(app.handle().clone());
-            
+
+            // Serve the control protocol on the single-instance socket
+            let handle = app.handle().clone();
+            control::start_listener(move |request| handle_control_request(&handle, request));
+
+            // Follow focus changes so text lands where the policy says
+            track_focus(app.handle().clone());
+
+            // Surface config problems instead of silently running on defaults
+            if !settings_errors.is_empty() {
+                let state = app.state::<AppState>();
+                let settings = state.settings.lock().unwrap().clone();
+                notify(
+                    app.handle(),
+                    &settings,
+                    "HyprWhisper config error",
+                    &settings_errors.join("\n"),
+                );
+            }
+
+            // Apply config.toml edits while running
+            let handle = app.handle().clone();
+            config::watch(move |reloaded| apply_reloaded_settings(&handle, reloaded));
+
             // Setup global shortcut
             if let Err(e) = setup_global_shortcut(app.handle()) {
                 eprintln!("Failed to setup global shortcut: {}", e);
@@ -601,6 +1271,7 @@ pub fn run() {
         })
         .invoke_handler(tauri::generate_handler![
             start_recording,
+            start_dictation,
             stop_recording,
             stop_recording_silent,
             get_audio_level,
@@ -616,12 +1287,18 @@ pub fn run() {
             delete_model,
             get_settings,
             save_settings,
+            add_vocabulary_term,
             get_input_devices,
             wtype_text,
             exit_app,
             finish_dictation,
             cancel_recording,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|_app, event| {
+            if let tauri::RunEvent::Exit = event {
+                control::remove_socket();
+            }
+        });
 }
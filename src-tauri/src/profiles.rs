@@ -0,0 +1,407 @@
+//! Per-application dictation profiles.
+//!
+//! A profile is picked when dictation starts by matching the target window's
+//! class and title. It can override settings for that dictation and rewrite
+//! the transcript before it is typed.
+
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::hyprland::Window;
+use crate::settings::Settings;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Capitalization {
+    /// Whatever Whisper produced
+    #[default]
+    Keep,
+    /// Everything lowercase, e.g. for shell commands
+    Lower,
+    /// Capitalize the first word and words after `.`, `!` and `?`
+    Sentence,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Punctuation {
+    #[default]
+    Keep,
+    /// Drop `.,!?;:` entirely
+    Strip,
+    /// Drop only a final period, as chat messages usually go without one
+    NoTrailingPeriod,
+}
+
+/// Whole-word, case-insensitive replacement
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Replacement {
+    pub from: String,
+    pub to: String,
+}
+
+/// A window pattern, compiled when the settings are loaded so an invalid one
+/// is rejected there rather than on every focus change
+#[derive(Debug, Clone)]
+pub struct Pattern(Regex);
+
+impl Pattern {
+    pub fn new(pattern: &str) -> Result<Self, String> {
+        Regex::new(pattern)
+            .map(Self)
+            .map_err(|e| format!("invalid pattern {:?}: {}", pattern, e))
+    }
+
+    pub fn is_match(&self, value: &str) -> bool {
+        self.0.is_match(value)
+    }
+}
+
+impl Serialize for Pattern {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.0.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Pattern {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let pattern = String::deserialize(deserializer)?;
+        Self::new(&pattern).map_err(serde::de::Error::custom)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    pub name: String,
+    /// Regex matched against the window class (e.g. `^(kitty|foot)$`)
+    pub class: Option<Pattern>,
+    /// Regex matched against the window title
+    pub title: Option<Pattern>,
+    pub language: Option<String>,
+    /// Model filename to dictate with
+    pub model: Option<String>,
+    pub text_injectors: Option<Vec<String>>,
+    /// Replaces the global `initial_prompt` for this application
+    pub prompt: Option<String>,
+    /// Added to the global vocabulary for this application
+    pub vocabulary: Vec<String>,
+    pub capitalization: Capitalization,
+    pub punctuation: Punctuation,
+    pub replacements: Vec<Replacement>,
+}
+
+impl Profile {
+    /// Both patterns (where given) must match; a profile without any
+    /// pattern never matches
+    pub fn matches(&self, window: &Window) -> bool {
+        if self.class.is_none() && self.title.is_none() {
+            return false;
+        }
+        let matches = |pattern: &Option<Pattern>, value: &str| {
+            pattern.as_ref().is_none_or(|p| p.is_match(value))
+        };
+        matches(&self.class, &window.class) && matches(&self.title, &window.title)
+    }
+
+    /// Apply this profile's setting overrides
+    pub fn apply(&self, settings: &mut Settings) {
+        if let Some(language) = &self.language {
+            settings.language = language.clone();
+        }
+        if let Some(model) = &self.model {
+            settings.model_filename = model.clone();
+        }
+        if let Some(injectors) = &self.text_injectors {
+            settings.text_injectors = injectors.clone();
+        }
+        if let Some(prompt) = &self.prompt {
+            settings.initial_prompt = prompt.clone();
+        }
+        for term in &self.vocabulary {
+            if !settings.vocabulary.contains(term) {
+                settings.vocabulary.push(term.clone());
+            }
+        }
+    }
+}
+
+/// First profile matching `window`
+pub fn select<'a>(profiles: &'a [Profile], window: &Window) -> Option<&'a Profile> {
+    profiles.iter().find(|p| p.matches(window))
+}
+
+/// Compiled whole-word replacements
+#[derive(Default)]
+pub struct Replacements {
+    rules: Vec<(Regex, String)>,
+}
+
+impl Replacements {
+    /// Compile `replacements`; bad ones are reported under `owner` and skipped
+    pub fn new(replacements: &[Replacement], owner: &str) -> Self {
+        let rules = replacements
+            .iter()
+            .filter(|r| !r.from.trim().is_empty())
+            .filter_map(|r| {
+                let pattern = whole_word(r.from.trim());
+                match RegexBuilder::new(&pattern).case_insensitive(true).build() {
+                    Ok(regex) => Some((regex, r.to.clone())),
+                    Err(e) => {
+                        eprintln!("{}: bad replacement {:?}: {}", owner, r.from, e);
+                        None
+                    }
+                }
+            })
+            .collect();
+
+        Self { rules }
+    }
+
+    pub fn apply(&self, text: &str) -> String {
+        let mut text = text.to_string();
+        for (regex, to) in &self.rules {
+            text = regex.replace_all(&text, regex::NoExpand(to)).into_owned();
+        }
+        text
+    }
+}
+
+/// Pattern for `term` as a whole word. `\b` only holds next to a word
+/// character, so terms like "C++" or ".NET" get a boundary on their word ends
+/// only.
+fn whole_word(term: &str) -> String {
+    let is_word = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric() || c == '_');
+    format!(
+        "{}{}{}",
+        if is_word(term.chars().next()) {
+            r"\b"
+        } else {
+            ""
+        },
+        regex::escape(term),
+        if is_word(term.chars().next_back()) {
+            r"\b"
+        } else {
+            ""
+        },
+    )
+}
+
+/// A profile's text rewriting with its patterns compiled
+#[derive(Default)]
+pub struct TextRules {
+    capitalization: Capitalization,
+    punctuation: Punctuation,
+    replacements: Replacements,
+}
+
+impl TextRules {
+    pub fn new(profile: &Profile) -> Self {
+        Self {
+            capitalization: profile.capitalization,
+            punctuation: profile.punctuation,
+            replacements: Replacements::new(
+                &profile.replacements,
+                &format!("Profile '{}'", profile.name),
+            ),
+        }
+    }
+
+    /// Rewrite a transcript. Applied to the whole session text each time, so
+    /// the same input always gives the same output.
+    pub fn apply(&self, text: &str) -> String {
+        let text = self.replacements.apply(text);
+
+        let text = match self.punctuation {
+            Punctuation::Keep => text,
+            Punctuation::Strip => text
+                .chars()
+                .filter(|c| !matches!(c, '.' | ',' | '!' | '?' | ';' | ':'))
+                .collect(),
+            Punctuation::NoTrailingPeriod => match text.trim_end().strip_suffix('.') {
+                // Keep an ellipsis
+                Some(rest) if !rest.ends_with('.') => rest.to_string(),
+                _ => text,
+            },
+        };
+
+        match self.capitalization {
+            Capitalization::Keep => text,
+            Capitalization::Lower => text.to_lowercase(),
+            Capitalization::Sentence => sentence_case(&text),
+        }
+    }
+}
+
+fn sentence_case(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut capitalize = true;
+    // Only a stop followed by whitespace ends a sentence, so `example.com` stays
+    let mut after_stop = false;
+
+    for c in text.chars() {
+        if c.is_whitespace() {
+            capitalize |= after_stop;
+            after_stop = false;
+            out.push(c);
+            continue;
+        }
+
+        if capitalize && c.is_alphabetic() {
+            out.extend(c.to_uppercase());
+        } else {
+            out.push(c);
+        }
+        capitalize = false;
+        after_stop = matches!(c, '.' | '!' | '?');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn window(class: &str, title: &str) -> Window {
+        Window {
+            address: "0x1".to_string(),
+            class: class.to_string(),
+            title: title.to_string(),
+            pid: 0,
+        }
+    }
+
+    fn profile(class: Option<&str>, title: Option<&str>) -> Profile {
+        Profile {
+            name: "Test".to_string(),
+            class: class.map(|p| Pattern::new(p).unwrap()),
+            title: title.map(|p| Pattern::new(p).unwrap()),
+            ..Default::default()
+        }
+    }
+
+    fn rules(capitalization: Capitalization, punctuation: Punctuation) -> TextRules {
+        TextRules::new(&Profile {
+            capitalization,
+            punctuation,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn matches_needs_every_given_pattern() {
+        let terminal = profile(Some("^(kitty|foot)$"), None);
+        assert!(terminal.matches(&window("kitty", "~")));
+        assert!(!terminal.matches(&window("kitty-extra", "~")));
+
+        let both = profile(Some("firefox"), Some("Slack"));
+        assert!(both.matches(&window("firefox", "Slack | general")));
+        assert!(!both.matches(&window("firefox", "Inbox")));
+        assert!(!both.matches(&window("chromium", "Slack | general")));
+    }
+
+    #[test]
+    fn a_profile_without_patterns_never_matches() {
+        assert!(!profile(None, None).matches(&window("kitty", "~")));
+    }
+
+    #[test]
+    fn select_picks_the_first_match() {
+        let mut first = profile(Some("kitty"), None);
+        first.name = "First".to_string();
+        let profiles = [profile(Some("foot"), None), first, profile(Some("."), None)];
+        assert_eq!(
+            select(&profiles, &window("kitty", "")).unwrap().name,
+            "First"
+        );
+    }
+
+    #[test]
+    fn invalid_patterns_are_rejected_when_loading() {
+        let error = serde_json::from_str::<Profile>(r#"{"name": "Bad", "class": "(kitty"}"#)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("invalid pattern"), "{}", error);
+
+        let profile: Profile = serde_json::from_str(r#"{"class": "^kitty$"}"#).unwrap();
+        assert_eq!(serde_json::to_value(&profile).unwrap()["class"], "^kitty$");
+    }
+
+    fn replace(from: &str, to: &str, text: &str) -> String {
+        let replacement = Replacement {
+            from: from.to_string(),
+            to: to.to_string(),
+        };
+        Replacements::new(&[replacement], "Test").apply(text)
+    }
+
+    #[test]
+    fn replacements_match_whole_words_in_any_case() {
+        assert_eq!(
+            replace("teh", "the", "Teh cat, teh. tehran"),
+            "the cat, the. tehran"
+        );
+        assert_eq!(
+            replace("new line", "$1", "a new  line, a new line"),
+            "a new  line, a $1"
+        );
+    }
+
+    #[test]
+    fn replacements_handle_terms_with_non_word_ends() {
+        assert_eq!(
+            replace("C++", "cpp", "I like C++ and c++."),
+            "I like cpp and cpp."
+        );
+        assert_eq!(replace("C++", "cpp", "ABC++"), "ABC++");
+        assert_eq!(replace(".NET", "dotnet", "use .NET now"), "use dotnet now");
+        assert_eq!(replace(".NET", "dotnet", "a.network"), "a.network");
+        assert_eq!(replace("@home", "at home", "I'm @home."), "I'm at home.");
+        assert_eq!(replace("@home", "at home", "@homepage"), "@homepage");
+    }
+
+    #[test]
+    fn sentence_case_capitalizes_after_stops_followed_by_space() {
+        assert_eq!(
+            sentence_case("hello. how are you? fine! see example.com"),
+            "Hello. How are you? Fine! See example.com"
+        );
+        assert_eq!(sentence_case("  \"quoted\" start"), "  \"quoted\" start");
+        assert_eq!(sentence_case("wait...\nnext"), "Wait...\nNext");
+        assert_eq!(sentence_case("ärger. öl"), "Ärger. Öl");
+    }
+
+    #[test]
+    fn text_rules_keep_by_default() {
+        let text = "Hello, World. Done.";
+        assert_eq!(TextRules::default().apply(text), text);
+    }
+
+    #[test]
+    fn text_rules_strip_punctuation_and_lowercase() {
+        let rules = rules(Capitalization::Lower, Punctuation::Strip);
+        assert_eq!(rules.apply("Git Status, Please!"), "git status please");
+    }
+
+    #[test]
+    fn text_rules_drop_only_a_single_trailing_period() {
+        let rules = rules(Capitalization::Keep, Punctuation::NoTrailingPeriod);
+        assert_eq!(rules.apply("See you. Bye. "), "See you. Bye");
+        assert_eq!(rules.apply("Well..."), "Well...");
+        assert_eq!(rules.apply("Really?"), "Really?");
+    }
+
+    #[test]
+    fn text_rules_replace_before_changing_case() {
+        let rules = TextRules::new(&Profile {
+            capitalization: Capitalization::Sentence,
+            replacements: vec![Replacement {
+                from: "new line".to_string(),
+                to: "newline. next".to_string(),
+            }],
+            ..Default::default()
+        });
+        assert_eq!(rules.apply("a New Line b"), "A newline. Next b");
+    }
+}
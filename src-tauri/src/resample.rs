@@ -0,0 +1,343 @@
+//! Band-limited sample rate conversion.
+//!
+//! A Kaiser-windowed sinc filter, evaluated from an oversampled table, so any
+//! pair of rates works. The cutoff sits just below the lower of the two
+//! Nyquist frequencies, which keeps 44.1/48kHz microphones from aliasing
+//! into the 16kHz audio Whisper gets.
+//!
+//! `Resampler` works on a stream: feeding it chunks gives exactly the same
+//! output as feeding it the whole recording at once. The filter is linear
+//! phase, so output sample `k` lines up with input time `k / target_rate`;
+//! producing it needs input up to ~1ms later (54 taps at 48kHz -> 16kHz),
+//! which `flush` releases at the end.
+
+use std::f64::consts::PI;
+
+/// Zero crossings of the sinc on each side; more means a steeper cutoff
+const ZERO_CROSSINGS: usize = 16;
+/// Fraction of the lower Nyquist frequency passed through
+const ROLLOFF: f64 = 0.9;
+/// Kaiser window shape; 8 gives roughly 80dB of stopband attenuation
+const KAISER_BETA: f64 = 8.0;
+/// Table entries per input sample
+const OVERSAMPLE: usize = 512;
+
+pub struct Resampler {
+    /// Rates reduced by their gcd, so positions are exact integers
+    from: u64,
+    to: u64,
+    /// Filter taps on each side of an output sample, in input samples
+    half: usize,
+    /// Right half of the kernel, sampled every 1/OVERSAMPLE input samples
+    kernel: Vec<f32>,
+    /// Input not yet fully used. `buffer[0]` is at position `buffer_start`;
+    /// input sample `n` is at `n + half`, after `half` samples of silence.
+    buffer: Vec<f32>,
+    buffer_start: u64,
+    /// Input samples received and output samples produced so far
+    received: u64,
+    produced: u64,
+}
+
+impl Resampler {
+    pub fn new(source_rate: u32, target_rate: u32) -> Self {
+        let divisor = gcd(source_rate as u64, target_rate as u64).max(1);
+        let from = source_rate as u64 / divisor;
+        let to = target_rate as u64 / divisor;
+
+        // Cutoff in cycles per input sample, times two (1.0 = input Nyquist)
+        let cutoff = ROLLOFF * (to as f64 / from as f64).min(1.0);
+        let half = (ZERO_CROSSINGS as f64 / cutoff).ceil() as usize;
+
+        let window_norm = bessel_i0(KAISER_BETA);
+        let kernel = (0..=half * OVERSAMPLE + 1)
+            .map(|i| {
+                let x = i as f64 / OVERSAMPLE as f64;
+                let t = x / half as f64;
+                if t > 1.0 {
+                    return 0.0;
+                }
+                let window = bessel_i0(KAISER_BETA * (1.0 - t * t).sqrt()) / window_norm;
+                (cutoff * sinc(cutoff * x) * window) as f32
+            })
+            .collect();
+
+        Self {
+            from,
+            to,
+            half,
+            kernel,
+            buffer: vec![0.0; half],
+            buffer_start: 0,
+            received: 0,
+            produced: 0,
+        }
+    }
+
+    /// Output samples produced so far
+    pub fn produced(&self) -> usize {
+        self.produced as usize
+    }
+
+    /// Resample the next chunk of input, returning the output it completes
+    pub fn process(&mut self, input: &[f32]) -> Vec<f32> {
+        if self.from == self.to {
+            self.received += input.len() as u64;
+            self.produced += input.len() as u64;
+            return input.to_vec();
+        }
+
+        self.buffer.extend_from_slice(input);
+        self.received += input.len() as u64;
+        self.drain(u64::MAX)
+    }
+
+    /// Output the tail still held back, as if the input were followed by
+    /// silence. The total matches `floor(input * target / source)`; the
+    /// resampler can't be used afterwards.
+    pub fn flush(&mut self) -> Vec<f32> {
+        if self.from == self.to {
+            return Vec::new();
+        }
+
+        let total = self.received * self.to / self.from;
+        self.buffer.extend(std::iter::repeat_n(0.0, 2 * self.half));
+        let output = self.drain(total);
+        self.buffer.clear();
+        output
+    }
+
+    /// Produce every output sample (up to `limit` in total) whose taps are
+    /// all in the buffer
+    fn drain(&mut self, limit: u64) -> Vec<f32> {
+        let mut output = Vec::new();
+        let available = self.buffer_start + self.buffer.len() as u64;
+
+        while self.produced < limit {
+            // Center of this output sample in input samples, offset by the
+            // leading silence
+            let position = self.produced * self.from;
+            let center = position / self.to + self.half as u64;
+            let frac = (position % self.to) as f64 / self.to as f64;
+
+            let first = center + 1 - self.half as u64;
+            let last = center + self.half as u64;
+            if last >= available {
+                break;
+            }
+
+            let start = (first - self.buffer_start) as usize;
+            let end = (last - self.buffer_start) as usize;
+            let taps = &self.buffer[start..=end];
+            let mut sum = 0.0f32;
+            for (i, sample) in taps.iter().enumerate() {
+                // Distance from the center, from -(half - 1) - frac to half - frac
+                let x = (i as f64 + 1.0 - self.half as f64) - frac;
+                sum += sample * self.tap(x);
+            }
+            output.push(sum);
+            self.produced += 1;
+        }
+
+        // Drop input that no future output sample reaches
+        let next_first = self.produced * self.from / self.to + 1;
+        let unused = (next_first.saturating_sub(self.buffer_start) as usize).min(self.buffer.len());
+        self.buffer.drain(..unused);
+        self.buffer_start += unused as u64;
+
+        output
+    }
+
+    /// Kernel value at `x` input samples from the center
+    fn tap(&self, x: f64) -> f32 {
+        let position = x.abs() * OVERSAMPLE as f64;
+        let index = position as usize;
+        if index + 1 >= self.kernel.len() {
+            return 0.0;
+        }
+        let frac = (position - index as f64) as f32;
+        self.kernel[index] * (1.0 - frac) + self.kernel[index + 1] * frac
+    }
+}
+
+/// Resample a whole recording
+pub fn resample(samples: &[f32], source_rate: u32, target_rate: u32) -> Vec<f32> {
+    if source_rate == target_rate || samples.is_empty() {
+        return samples.to_vec();
+    }
+
+    let mut resampler = Resampler::new(source_rate, target_rate);
+    let mut output = resampler.process(samples);
+    output.extend(resampler.flush());
+    output
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-9 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+/// Modified Bessel function of the first kind, order 0
+fn bessel_i0(x: f64) -> f64 {
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    let half_x = x / 2.0;
+    for k in 1..50 {
+        term *= (half_x / k as f64).powi(2);
+        sum += term;
+        if term < sum * 1e-12 {
+            break;
+        }
+    }
+    sum
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(freq: f64, rate: u32, len: usize) -> Vec<f32> {
+        (0..len)
+            .map(|n| (2.0 * PI * freq * n as f64 / rate as f64).sin() as f32)
+            .collect()
+    }
+
+    /// RMS over the middle half, away from the edges
+    fn rms(samples: &[f32]) -> f64 {
+        let middle = &samples[samples.len() / 4..samples.len() * 3 / 4];
+        (middle.iter().map(|s| (*s as f64).powi(2)).sum::<f64>() / middle.len() as f64).sqrt()
+    }
+
+    fn decibels(ratio: f64) -> f64 {
+        20.0 * ratio.log10()
+    }
+
+    #[test]
+    fn passband_keeps_amplitude_and_timing() {
+        let output = resample(&sine(1000.0, 48000, 48000), 48000, 16000);
+        let expected = sine(1000.0, 16000, output.len());
+
+        let gain = decibels(rms(&output) / rms(&expected));
+        assert!(gain.abs() < 0.1, "1kHz gain {:.3}dB", gain);
+
+        // Output sample k lines up with input time k / 16000: no delay
+        let error: Vec<f32> = output.iter().zip(&expected).map(|(a, b)| a - b).collect();
+        assert!(decibels(rms(&error) / rms(&expected)) < -40.0);
+    }
+
+    #[test]
+    fn passband_edge_is_flat() {
+        for freq in [100.0, 3000.0, 6000.0] {
+            let output = resample(&sine(freq, 48000, 48000), 48000, 16000);
+            let gain = decibels(rms(&output) / (0.5f64).sqrt());
+            assert!(gain.abs() < 0.5, "{}Hz gain {:.3}dB", freq, gain);
+        }
+    }
+
+    #[test]
+    fn tones_above_nyquist_are_removed() {
+        for freq in [9000.0, 12000.0, 20000.0] {
+            let output = resample(&sine(freq, 48000, 48000), 48000, 16000);
+            let gain = decibels(rms(&output) / (0.5f64).sqrt());
+            assert!(gain < -75.0, "{}Hz only attenuated to {:.1}dB", freq, gain);
+        }
+
+        let output = resample(&sine(10000.0, 44100, 44100), 44100, 16000);
+        let gain = decibels(rms(&output) / (0.5f64).sqrt());
+        assert!(
+            gain < -75.0,
+            "44.1kHz: 10kHz only attenuated to {:.1}dB",
+            gain
+        );
+    }
+
+    #[test]
+    fn streaming_matches_one_call() {
+        let input = sine(440.0, 44100, 30000);
+        let whole = resample(&input, 44100, 16000);
+
+        // Deterministic pseudo-random chunk sizes from 0 to ~2000
+        let mut seed = 12345u64;
+        let mut resampler = Resampler::new(44100, 16000);
+        let mut streamed = Vec::new();
+        let mut rest = &input[..];
+        while !rest.is_empty() {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            let size = ((seed >> 33) as usize % 2000).min(rest.len());
+            streamed.extend(resampler.process(&rest[..size]));
+            rest = &rest[size..];
+        }
+        streamed.extend(resampler.flush());
+
+        assert_eq!(streamed, whole);
+        assert_eq!(resampler.produced(), whole.len());
+    }
+
+    #[test]
+    fn output_length_is_exact() {
+        for (len, from, to) in [
+            (48000, 48000, 16000),
+            (48001, 48000, 16000),
+            (1, 48000, 16000),
+            (44100, 44100, 16000),
+            (12345, 44100, 16000),
+            (999, 8000, 16000),
+            (7, 22050, 16000),
+        ] {
+            let output = resample(&vec![0.1; len], from, to);
+            assert_eq!(
+                output.len() as u64,
+                len as u64 * to as u64 / from as u64,
+                "{} samples {} -> {}",
+                len,
+                from,
+                to
+            );
+        }
+        assert!(resample(&[], 48000, 16000).is_empty());
+    }
+
+    #[test]
+    fn holds_back_half_the_filter() {
+        let mut resampler = Resampler::new(48000, 16000);
+        // ~1.1ms at 48kHz, as the module docs say
+        assert_eq!(resampler.half, 54);
+
+        let mut received = 0u64;
+        for size in [10, 50, 100, 1000, 3, 4800] {
+            resampler.process(&vec![0.0; size]);
+            received += size as u64;
+
+            // Output sample k needs input up to floor(k * 3) + half
+            let produced = resampler.produced() as u64;
+            let half = resampler.half as u64;
+            if produced > 0 {
+                assert!((produced - 1) * 3 + half < received);
+            }
+            assert!(produced * 3 + half >= received);
+        }
+    }
+
+    #[test]
+    fn same_rate_passes_through() {
+        let input = sine(1000.0, 16000, 1000);
+        assert_eq!(resample(&input, 16000, 16000), input);
+
+        let mut resampler = Resampler::new(16000, 16000);
+        assert_eq!(resampler.process(&input), input);
+        assert!(resampler.flush().is_empty());
+    }
+}
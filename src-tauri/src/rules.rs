@@ -0,0 +1,308 @@
+//! User text rules, applied in order as the last step before text is typed.
+//!
+//! Replacement text may contain templates, filled in when dictation starts:
+//! `{date}`, `{time}`, `{datetime}`, or a strftime format such as
+//! `{date:%d.%m.%Y}`. Anything else in braces is left alone.
+
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Local};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum Rule {
+    /// Replace every exact occurrence of `from`
+    Literal { from: String, to: String },
+    /// Regex replacement; `to` can refer to groups as `$1` or `${name}`
+    Regex { pattern: String, to: String },
+    /// Replace a spoken phrase (whole words, any case) with a snippet,
+    /// e.g. "insert signature" with a multi-line signature
+    Snippet { phrase: String, text: String },
+}
+
+impl Rule {
+    /// Short description for logs and the dry run
+    pub fn describe(&self) -> String {
+        match self {
+            Rule::Literal { from, .. } => format!("literal {:?}", from),
+            Rule::Regex { pattern, .. } => format!("regex {:?}", pattern),
+            Rule::Snippet { phrase, .. } => format!("snippet {:?}", phrase),
+        }
+    }
+}
+
+enum Compiled {
+    Literal {
+        from: String,
+        to: String,
+    },
+    Regex {
+        regex: Regex,
+        to: String,
+        expand: bool,
+    },
+}
+
+/// Rules compiled with their templates filled in
+#[derive(Default)]
+pub struct Rules {
+    /// Each rule with its position in the config
+    compiled: Vec<(usize, Compiled)>,
+}
+
+impl Rules {
+    /// Compile `rules`, filling templates with `now`. Invalid rules are
+    /// reported and skipped.
+    pub fn new(rules: &[Rule], now: DateTime<Local>) -> Self {
+        let compiled = rules
+            .iter()
+            .enumerate()
+            .filter_map(|(index, rule)| match compile(rule, &now) {
+                Ok(compiled) => Some((index, compiled)),
+                Err(e) => {
+                    eprintln!("Rule {} ({}): {}", index + 1, rule.describe(), e);
+                    None
+                }
+            })
+            .collect();
+
+        Self { compiled }
+    }
+
+    pub fn apply(&self, text: &str) -> String {
+        self.trace(text)
+            .pop()
+            .map(|(_, text)| text)
+            .unwrap_or_else(|| text.to_string())
+    }
+
+    /// Apply the rules and return, for each rule that changed the text, its
+    /// position in the config and the text after it
+    pub fn trace(&self, text: &str) -> Vec<(usize, String)> {
+        let mut steps = Vec::new();
+        let mut text = text.to_string();
+
+        for (index, rule) in &self.compiled {
+            let next = match rule {
+                Compiled::Literal { from, to } => text.replace(from.as_str(), to),
+                Compiled::Regex {
+                    regex,
+                    to,
+                    expand: true,
+                } => regex.replace_all(&text, to.as_str()).into_owned(),
+                Compiled::Regex {
+                    regex,
+                    to,
+                    expand: false,
+                } => regex.replace_all(&text, regex::NoExpand(to)).into_owned(),
+            };
+            if next != text {
+                steps.push((*index, next.clone()));
+                text = next;
+            }
+        }
+        steps
+    }
+}
+
+fn compile(rule: &Rule, now: &DateTime<Local>) -> Result<Compiled, String> {
+    match rule {
+        Rule::Literal { from, to } => {
+            if from.is_empty() {
+                return Err("empty 'from'".to_string());
+            }
+            Ok(Compiled::Literal {
+                from: from.clone(),
+                to: fill_templates(to, now)?,
+            })
+        }
+        Rule::Regex { pattern, to } => Ok(Compiled::Regex {
+            regex: Regex::new(pattern).map_err(|e| e.to_string())?,
+            to: fill_templates(to, now)?,
+            expand: true,
+        }),
+        Rule::Snippet { phrase, text } => {
+            let words: Vec<String> = phrase.split_whitespace().map(regex::escape).collect();
+            if words.is_empty() {
+                return Err("empty phrase".to_string());
+            }
+            // Whisper punctuates phrases ("Insert signature."); take that along
+            let pattern = format!(r"\b{}\b[.,!?]?", words.join(r"[\s,]+"));
+            Ok(Compiled::Regex {
+                regex: RegexBuilder::new(&pattern)
+                    .case_insensitive(true)
+                    .build()
+                    .map_err(|e| e.to_string())?,
+                to: fill_templates(text, now)?,
+                expand: false,
+            })
+        }
+    }
+}
+
+/// Replace `{date}`, `{time}`, `{datetime}` and their `{name:format}` forms
+fn fill_templates(text: &str, now: &DateTime<Local>) -> Result<String, String> {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+
+        let Some(end) = after.find('}') else {
+            rest = &rest[start..];
+            break;
+        };
+        let inner = &after[..end];
+        let (name, format) = match inner.split_once(':') {
+            Some((name, format)) => (name, Some(format)),
+            None => (inner, None),
+        };
+
+        let default = match name {
+            "date" => Some("%Y-%m-%d"),
+            "time" => Some("%H:%M"),
+            "datetime" => Some("%Y-%m-%d %H:%M"),
+            _ => None,
+        };
+        match default {
+            Some(default) => out.push_str(&format_time(now, format.unwrap_or(default))?),
+            // Not ours, e.g. `${name}` in a regex replacement
+            None => {
+                out.push('{');
+                out.push_str(inner);
+                out.push('}');
+            }
+        }
+        rest = &after[end + 1..];
+    }
+
+    out.push_str(rest);
+    Ok(out)
+}
+
+fn format_time(now: &DateTime<Local>, format: &str) -> Result<String, String> {
+    let items: Vec<Item> = StrftimeItems::new(format).collect();
+    // Formatting an invalid specifier would panic
+    if items.iter().any(|item| matches!(item, Item::Error)) {
+        return Err(format!("invalid date format {:?}", format));
+    }
+    Ok(now.format_with_items(items.into_iter()).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn now() -> DateTime<Local> {
+        Local.with_ymd_and_hms(2024, 3, 5, 14, 7, 9).unwrap()
+    }
+
+    fn literal(from: &str, to: &str) -> Rule {
+        Rule::Literal {
+            from: from.to_string(),
+            to: to.to_string(),
+        }
+    }
+
+    fn regex(pattern: &str, to: &str) -> Rule {
+        Rule::Regex {
+            pattern: pattern.to_string(),
+            to: to.to_string(),
+        }
+    }
+
+    fn snippet(phrase: &str, text: &str) -> Rule {
+        Rule::Snippet {
+            phrase: phrase.to_string(),
+            text: text.to_string(),
+        }
+    }
+
+    fn apply(rules: &[Rule], text: &str) -> String {
+        Rules::new(rules, now()).apply(text)
+    }
+
+    #[test]
+    fn literal_rules_replace_exact_text_only() {
+        let rules = [literal("(c)", "©")];
+        assert_eq!(apply(&rules, "(c) 2024, (C) too"), "© 2024, (C) too");
+    }
+
+    #[test]
+    fn regex_rules_expand_groups() {
+        let rules = [regex(r"(\d+) percent", "$1%")];
+        assert_eq!(apply(&rules, "up 5 percent"), "up 5%");
+
+        let rules = [regex(r"(?P<word>\w+)!", "${word}?")];
+        assert_eq!(apply(&rules, "what!"), "what?");
+    }
+
+    #[test]
+    fn snippets_insert_dollar_signs_literally() {
+        let rules = [snippet("price", "$1 or ${name}")];
+        assert_eq!(apply(&rules, "the price"), "the $1 or ${name}");
+    }
+
+    #[test]
+    fn snippets_match_whole_phrases_with_punctuation() {
+        let rules = [snippet("new line", "\n")];
+        assert_eq!(apply(&rules, "one New line, two"), "one \n two");
+        assert_eq!(apply(&rules, "one new, line. two"), "one \n two");
+        assert_eq!(apply(&rules, "renew linear"), "renew linear");
+        assert_eq!(apply(&rules, "a new lines"), "a new lines");
+    }
+
+    #[test]
+    fn trace_reports_rules_in_order_of_the_config() {
+        let rules = [literal("a", "b"), literal("x", "y"), literal("b", "c")];
+        let rules = Rules::new(&rules, now());
+        assert_eq!(
+            rules.trace("a"),
+            [(0, "b".to_string()), (2, "c".to_string())]
+        );
+        assert!(rules.trace("nothing").is_empty());
+        assert_eq!(rules.apply("nothing"), "nothing");
+    }
+
+    #[test]
+    fn templates_are_filled_when_compiling() {
+        let rules = [
+            literal("today", "{date}"),
+            literal("now", "{time}"),
+            literal("stamp", "{datetime}"),
+            literal("german", "{date:%d.%m.%Y}"),
+        ];
+        assert_eq!(
+            apply(&rules, "today now stamp german"),
+            "2024-03-05 14:07 2024-03-05 14:07 05.03.2024"
+        );
+    }
+
+    #[test]
+    fn unknown_templates_are_left_alone() {
+        assert_eq!(
+            fill_templates("{name} {date", &now()).unwrap(),
+            "{name} {date"
+        );
+        assert_eq!(fill_templates("${1}", &now()).unwrap(), "${1}");
+    }
+
+    #[test]
+    fn invalid_rules_are_reported_and_skipped() {
+        assert!(compile(&literal("x", "{date:%Q}"), &now()).is_err());
+        assert!(compile(&regex("(unclosed", "x"), &now()).is_err());
+        assert!(compile(&literal("", "x"), &now()).is_err());
+        assert!(compile(&snippet("  ", "x"), &now()).is_err());
+
+        let rules = [
+            regex("(unclosed", "x"),
+            literal("a", "{time:%Q}"),
+            literal("a", "b"),
+        ];
+        let rules = Rules::new(&rules, now());
+        assert_eq!(rules.trace("a"), [(2, "b".to_string())]);
+    }
+}
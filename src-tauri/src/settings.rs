@@ -0,0 +1,409 @@
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::focus::FocusPolicy;
+use crate::history::Retention;
+use crate::profiles::{Profile, Replacement};
+use crate::rules::Rule;
+use crate::voice_commands::CustomCommand;
+
+/// Bump when a field is renamed, removed or changes meaning, and add a step
+/// to `migrate`. Newly added fields don't need a bump: `#[serde(default)]`
+/// fills them from `Settings::default()`.
+pub const SETTINGS_VERSION: u32 = 1;
+
+/// On-disk layout: `{ "version": N, "settings": { ... } }`
+#[derive(Serialize)]
+struct SettingsFile<'a> {
+    version: u32,
+    settings: &'a Settings,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    #[serde(alias = "model")]
+    pub model_filename: String,
+    pub language: String,
+    pub hotkey: String,
+    /// Type the transcript into the focused window. When off, the final
+    /// transcript only goes to the clipboard.
+    pub auto_paste: bool,
+    pub show_notification: bool,
+    /// Emit speech-start / speech-end events while recording
+    pub vad_enabled: bool,
+    /// Stop dictation after this much silence following speech (0 = never).
+    /// The clock starts at the first speech-end, so silence before anything
+    /// was said never stops a recording.
+    pub auto_stop_silence_ms: u64,
+    /// Consecutive live hypotheses that must agree before a word is typed
+    pub local_agreement: usize,
+    /// Preferred input device name (exact or substring match), `None` for the system default
+    pub input_device: Option<String>,
+    /// Use the system default when `input_device` is missing instead of failing
+    pub input_device_fallback: bool,
+    /// Text injection backends to try, in order (see `inject::INJECTOR_NAMES`)
+    pub text_injectors: Vec<String>,
+    /// Clipboard mode: also set the primary (middle-click) selection
+    pub copy_to_primary: bool,
+    /// Clipboard mode: press Ctrl+V in the target after copying
+    pub paste_from_clipboard: bool,
+    /// Clipboard mode: put the previous clipboard back after pasting
+    pub restore_clipboard: bool,
+    /// What to do when focus moves away from the target window mid-dictation
+    pub focus_policy: FocusPolicy,
+    /// Per-application overrides, the first match on the target window wins
+    pub profiles: Vec<Profile>,
+    /// Act on spoken commands like "new line", "comma" or "scratch that"
+    pub voice_commands: bool,
+    /// Extra spoken phrases, checked before the built-in ones
+    pub custom_voice_commands: Vec<CustomCommand>,
+    /// Names and jargon Whisper should spell this way, passed to it in the
+    /// initial prompt
+    pub vocabulary: Vec<String>,
+    /// Text fed to Whisper ahead of the vocabulary, e.g. a sentence written
+    /// the way you dictate
+    pub initial_prompt: String,
+    /// Whole-word fixes for known mis-hearings, applied to every transcript
+    pub corrections: Vec<Replacement>,
+    /// Replacements and snippets applied in order right before typing
+    pub rules: Vec<Rule>,
+    /// Keep a log of finished dictations (see `history`)
+    pub history: bool,
+    /// Entries kept in the history (0 = no limit)
+    pub history_max_entries: usize,
+    /// Days an entry is kept (0 = forever)
+    pub history_max_age_days: u32,
+    /// How long after a dictation `undo-last` may still delete it
+    pub undo_max_age_secs: u64,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            model_filename: "ggml-base.bin".to_string(),
+            language: "auto".to_string(),
+            hotkey: "Ctrl+Shift+.".to_string(),
+            auto_paste: true,
+            show_notification: true,
+            vad_enabled: true,
+            auto_stop_silence_ms: 0,
+            local_agreement: crate::dictation::DEFAULT_AGREEMENT,
+            input_device: None,
+            input_device_fallback: true,
+            text_injectors: vec![
+                "native".to_string(),
+                "wtype".to_string(),
+                "ydotool".to_string(),
+                "clipboard".to_string(),
+            ],
+            copy_to_primary: false,
+            paste_from_clipboard: false,
+            restore_clipboard: true,
+            focus_policy: FocusPolicy::default(),
+            profiles: Vec::new(),
+            voice_commands: true,
+            custom_voice_commands: Vec::new(),
+            vocabulary: Vec::new(),
+            initial_prompt: String::new(),
+            corrections: Vec::new(),
+            rules: Vec::new(),
+            history: true,
+            history_max_entries: 1000,
+            history_max_age_days: 90,
+            undo_max_age_secs: 120,
+        }
+    }
+}
+
+impl Settings {
+    /// Language to pass to Whisper, `None` for auto-detect
+    pub fn whisper_language(&self) -> Option<String> {
+        if self.language == "auto" {
+            None
+        } else {
+            Some(self.language.clone())
+        }
+    }
+
+    pub fn history_retention(&self) -> Retention {
+        Retention {
+            max_entries: self.history_max_entries,
+            max_age_days: self.history_max_age_days,
+        }
+    }
+
+    /// Initial prompt for Whisper: `initial_prompt` followed by the vocabulary
+    pub fn whisper_prompt(&self) -> String {
+        let vocabulary = self
+            .vocabulary
+            .iter()
+            .map(|term| term.trim())
+            .filter(|term| !term.is_empty())
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        match (self.initial_prompt.trim(), vocabulary.as_str()) {
+            (prompt, "") => prompt.to_string(),
+            ("", vocabulary) => format!("{}.", vocabulary),
+            (prompt, vocabulary) => format!("{} {}.", prompt, vocabulary),
+        }
+    }
+
+    /// Add `term` to the vocabulary and, if given, a correction from what
+    /// Whisper `heard` instead. Returns false if there was nothing to add.
+    pub fn add_vocabulary_term(&mut self, term: &str, heard: Option<&str>) -> bool {
+        let term = term.trim();
+        if term.is_empty() {
+            return false;
+        }
+
+        let mut changed = false;
+        if !self.vocabulary.iter().any(|t| t == term) {
+            self.vocabulary.push(term.to_string());
+            changed = true;
+        }
+
+        if let Some(heard) = heard.map(str::trim).filter(|h| !h.is_empty() && *h != term) {
+            match self
+                .corrections
+                .iter_mut()
+                .find(|c| c.from.eq_ignore_ascii_case(heard))
+            {
+                Some(correction) if correction.to == term => {}
+                Some(correction) => {
+                    correction.to = term.to_string();
+                    changed = true;
+                }
+                None => {
+                    self.corrections.push(Replacement {
+                        from: heard.to_string(),
+                        to: term.to_string(),
+                    });
+                    changed = true;
+                }
+            }
+        }
+        changed
+    }
+}
+
+pub fn get_config_directory() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("hyprwhisper")
+}
+
+pub fn get_settings_path() -> PathBuf {
+    get_config_directory().join("settings.json")
+}
+
+/// Load settings from disk. A missing file yields the defaults.
+pub fn load() -> Result<Settings, String> {
+    load_from(&get_settings_path())
+}
+
+fn load_from(path: &Path) -> Result<Settings, String> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Settings::default()),
+        Err(e) => return Err(format!("Failed to read {:?}: {}", path, e)),
+    };
+
+    let file: serde_json::Value = serde_json::from_str(&contents)
+        .map_err(|e| format!("Failed to parse {:?}: {}", path, e))?;
+
+    let version = file.get("version").and_then(|v| v.as_u64()).unwrap_or(0) as u32;
+
+    let settings = migrate(version, file)?;
+    serde_json::from_value(settings).map_err(|e| format!("Invalid settings in {:?}: {}", path, e))
+}
+
+/// Add a vocabulary term (and correction) to settings.json. Lists in
+/// config.toml still take precedence.
+pub fn add_vocabulary_term(term: &str, heard: Option<&str>) -> Result<(), String> {
+    let mut saved = load()?;
+    if saved.add_vocabulary_term(term, heard) {
+        save(&saved)?;
+    }
+    Ok(())
+}
+
+/// Write settings to disk atomically: a temp file in the same directory is
+/// fully written and synced, then renamed over the old file.
+pub fn save(settings: &Settings) -> Result<(), String> {
+    save_to(&get_settings_path(), settings)
+}
+
+fn save_to(path: &Path, settings: &Settings) -> Result<(), String> {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {:?}: {}", dir, e))?;
+
+    let contents = serde_json::to_vec_pretty(&SettingsFile {
+        version: SETTINGS_VERSION,
+        settings,
+    })
+    .map_err(|e| format!("Failed to serialize settings: {}", e))?;
+
+    let mut file = tempfile::NamedTempFile::new_in(dir)
+        .map_err(|e| format!("Failed to create temp file: {}", e))?;
+    file.write_all(&contents)
+        .map_err(|e| format!("Failed to write settings: {}", e))?;
+    file.as_file()
+        .sync_all()
+        .map_err(|e| format!("Failed to sync settings: {}", e))?;
+    file.persist(path)
+        .map_err(|e| format!("Failed to save settings: {}", e))?;
+
+    Ok(())
+}
+
+/// Take the settings object out of a file written by any version, brought
+/// up to `SETTINGS_VERSION`
+fn migrate(version: u32, file: serde_json::Value) -> Result<serde_json::Value, String> {
+    let settings = match version {
+        // Unversioned: the file is a flat object of settings keys
+        0 => file,
+        SETTINGS_VERSION => settings_object(file),
+        newer => {
+            eprintln!(
+                "Settings file version {} is newer than supported ({}), loading known fields",
+                newer, SETTINGS_VERSION
+            );
+            settings_object(file)
+        }
+    };
+
+    if !settings.is_object() {
+        return Err("Settings must be a JSON object".to_string());
+    }
+    Ok(settings)
+}
+
+/// The `settings` member of a versioned file. Without one the keys are
+/// probably at the top level (hand-edited), so those are used rather than
+/// silently resetting everything to the defaults.
+fn settings_object(mut file: serde_json::Value) -> serde_json::Value {
+    match file.get_mut("settings").map(serde_json::Value::take) {
+        Some(settings) => settings,
+        None => {
+            eprintln!("Settings file has a version but no \"settings\" object, reading its top-level keys");
+            file
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn load_json(dir: &Path, value: serde_json::Value) -> Result<Settings, String> {
+        let path = dir.join("settings.json");
+        std::fs::write(&path, value.to_string()).unwrap();
+        load_from(&path)
+    }
+
+    #[test]
+    fn unversioned_files_are_the_settings_themselves() {
+        let flat = json!({ "model": "ggml-small.bin", "language": "de" });
+        assert_eq!(migrate(0, flat.clone()), Ok(flat.clone()));
+
+        let dir = tempfile::tempdir().unwrap();
+        let settings = load_json(dir.path(), flat).unwrap();
+        assert_eq!(settings.model_filename, "ggml-small.bin");
+        assert_eq!(settings.language, "de");
+        // Everything else keeps its default
+        assert_eq!(settings.hotkey, Settings::default().hotkey);
+    }
+
+    #[test]
+    fn current_files_keep_their_settings_under_a_key() {
+        let file = json!({ "version": SETTINGS_VERSION, "settings": { "language": "fr" } });
+        assert_eq!(
+            migrate(SETTINGS_VERSION, file),
+            Ok(json!({ "language": "fr" }))
+        );
+    }
+
+    #[test]
+    fn a_versioned_file_without_settings_reads_its_top_level_keys() {
+        let file = json!({ "version": SETTINGS_VERSION, "language": "es" });
+        assert_eq!(migrate(SETTINGS_VERSION, file.clone()), Ok(file.clone()));
+
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(load_json(dir.path(), file).unwrap().language, "es");
+    }
+
+    #[test]
+    fn newer_files_load_the_fields_we_know() {
+        let dir = tempfile::tempdir().unwrap();
+        let settings = load_json(
+            dir.path(),
+            json!({
+                "version": SETTINGS_VERSION + 1,
+                "settings": { "language": "it", "from_the_future": true }
+            }),
+        )
+        .unwrap();
+        assert_eq!(settings.language, "it");
+    }
+
+    #[test]
+    fn settings_must_be_an_object() {
+        assert!(migrate(0, json!([1, 2])).is_err());
+        assert!(migrate(SETTINGS_VERSION, json!({ "settings": "none" })).is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("settings.json");
+        std::fs::write(&path, "{ not json").unwrap();
+        assert!(load_from(&path).unwrap_err().starts_with("Failed to parse"));
+    }
+
+    #[test]
+    fn a_missing_file_gives_the_defaults() {
+        let dir = tempfile::tempdir().unwrap();
+        let settings = load_from(&dir.path().join("settings.json")).unwrap();
+        assert_eq!(settings.model_filename, Settings::default().model_filename);
+    }
+
+    #[test]
+    fn save_writes_a_versioned_file_that_loads_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("hyprwhisper").join("settings.json");
+
+        let mut settings = Settings {
+            language: "nl".to_string(),
+            ..Settings::default()
+        };
+        settings.add_vocabulary_term("Hyprland", Some("hyper land"));
+        save_to(&path, &settings).unwrap();
+
+        let file: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(file["version"], SETTINGS_VERSION);
+        assert_eq!(file["settings"]["language"], "nl");
+
+        let loaded = load_from(&path).unwrap();
+        assert_eq!(
+            serde_json::to_value(&loaded).unwrap(),
+            serde_json::to_value(&settings).unwrap()
+        );
+    }
+
+    #[test]
+    fn save_replaces_the_file_without_leaving_temp_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("settings.json");
+        std::fs::write(&path, json!({ "language": "de" }).to_string()).unwrap();
+
+        let mut settings = load_from(&path).unwrap();
+        settings.language = "sv".to_string();
+        save_to(&path, &settings).unwrap();
+
+        assert_eq!(load_from(&path).unwrap().language, "sv");
+        let entries: Vec<_> = std::fs::read_dir(dir.path()).unwrap().collect();
+        assert_eq!(entries.len(), 1);
+    }
+}
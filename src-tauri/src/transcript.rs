@@ -0,0 +1,251 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+
+/// A decoded token with Whisper's confidence in it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Token {
+    pub text: String,
+    pub probability: f32,
+}
+
+/// A stretch of transcribed audio, timed relative to the start of the input
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Segment {
+    pub start_ms: i64,
+    pub end_ms: i64,
+    pub text: String,
+    /// Mean probability of the segment's text tokens
+    pub probability: f32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tokens: Vec<Token>,
+}
+
+/// Full result of transcribing a piece of audio
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Transcript {
+    /// Language code Whisper was told to use, or detected
+    pub language: Option<String>,
+    pub segments: Vec<Segment>,
+}
+
+/// Ways a transcript can be written out
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    #[default]
+    Txt,
+    Srt,
+    Vtt,
+    Json,
+}
+
+impl OutputFormat {
+    /// Guess the format from an output file's extension
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_lowercase().as_str() {
+            "txt" => Some(Self::Txt),
+            "srt" => Some(Self::Srt),
+            "vtt" => Some(Self::Vtt),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+}
+
+impl Transcript {
+    pub fn text(&self) -> String {
+        self.segments
+            .iter()
+            .map(|s| s.text.as_str())
+            .filter(|t| !t.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    pub fn render(&self, format: OutputFormat) -> String {
+        match format {
+            OutputFormat::Txt => self.text(),
+            OutputFormat::Srt => self.to_srt(),
+            OutputFormat::Vtt => self.to_vtt(),
+            OutputFormat::Json => self.to_json(),
+        }
+    }
+
+    /// SubRip cues, numbered from 1
+    pub fn to_srt(&self) -> String {
+        let mut out = String::new();
+        for (i, segment) in self.cues().enumerate() {
+            let _ = write!(
+                out,
+                "{}\n{} --> {}\n{}\n\n",
+                i + 1,
+                timestamp(segment.start_ms, ','),
+                timestamp(segment.end_ms, ','),
+                segment.text
+            );
+        }
+        out
+    }
+
+    /// WebVTT with cue text escaped
+    pub fn to_vtt(&self) -> String {
+        let mut out = String::from("WEBVTT\n\n");
+        for segment in self.cues() {
+            let _ = write!(
+                out,
+                "{} --> {}\n{}\n\n",
+                timestamp(segment.start_ms, '.'),
+                timestamp(segment.end_ms, '.'),
+                escape_vtt(&segment.text)
+            );
+        }
+        out
+    }
+
+    /// The transcript plus its joined text, pretty-printed
+    pub fn to_json(&self) -> String {
+        let value = serde_json::json!({
+            "language": self.language,
+            "text": self.text(),
+            "segments": self.segments,
+        });
+        serde_json::to_string_pretty(&value).unwrap_or_default()
+    }
+
+    /// Segments worth a subtitle cue
+    fn cues(&self) -> impl Iterator<Item = &Segment> {
+        self.segments.iter().filter(|s| !s.text.is_empty())
+    }
+}
+
+/// `HH:MM:SS,mmm` (SRT) or `HH:MM:SS.mmm` (WebVTT)
+fn timestamp(ms: i64, separator: char) -> String {
+    let ms = ms.max(0);
+    format!(
+        "{:02}:{:02}:{:02}{}{:03}",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        separator,
+        ms % 1000
+    )
+}
+
+fn escape_vtt(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(start_ms: i64, end_ms: i64, text: &str) -> Segment {
+        Segment {
+            start_ms,
+            end_ms,
+            text: text.to_string(),
+            probability: 0.9,
+            tokens: Vec::new(),
+        }
+    }
+
+    fn transcript(segments: Vec<Segment>) -> Transcript {
+        Transcript {
+            language: Some("en".to_string()),
+            segments,
+        }
+    }
+
+    #[test]
+    fn timestamps_roll_over_into_seconds_minutes_and_hours() {
+        assert_eq!(timestamp(0, ','), "00:00:00,000");
+        assert_eq!(timestamp(999, ','), "00:00:00,999");
+        assert_eq!(timestamp(1_000, '.'), "00:00:01.000");
+        assert_eq!(timestamp(59_999, '.'), "00:00:59.999");
+        assert_eq!(timestamp(60_000, ','), "00:01:00,000");
+        assert_eq!(timestamp(3_599_999, ','), "00:59:59,999");
+        assert_eq!(timestamp(3_600_000, '.'), "01:00:00.000");
+        assert_eq!(timestamp(100 * 3_600_000 + 61_001, ','), "100:01:01,001");
+        // Whisper can report slightly negative starts
+        assert_eq!(timestamp(-20, ','), "00:00:00,000");
+    }
+
+    #[test]
+    fn srt_numbers_cues_and_uses_commas() {
+        let transcript = transcript(vec![
+            segment(0, 1500, "Hello there."),
+            segment(1500, 2000, ""),
+            segment(3_599_500, 3_601_250, "An hour in."),
+        ]);
+        assert_eq!(
+            transcript.to_srt(),
+            "1\n00:00:00,000 --> 00:00:01,500\nHello there.\n\n\
+             2\n00:59:59,500 --> 01:00:01,250\nAn hour in.\n\n"
+        );
+    }
+
+    #[test]
+    fn vtt_uses_dots_and_escapes_cue_text() {
+        let transcript = transcript(vec![
+            segment(250, 1000, "a <b> & c"),
+            segment(1000, 2000, "x --> y"),
+        ]);
+        assert_eq!(
+            transcript.to_vtt(),
+            "WEBVTT\n\n\
+             00:00:00.250 --> 00:00:01.000\na &lt;b&gt; &amp; c\n\n\
+             00:00:01.000 --> 00:00:02.000\nx --&gt; y\n\n"
+        );
+    }
+
+    #[test]
+    fn srt_does_not_escape() {
+        let transcript = transcript(vec![segment(0, 1000, "a < b & c")]);
+        assert!(transcript.to_srt().contains("\na < b & c\n"));
+    }
+
+    #[test]
+    fn empty_transcripts() {
+        let empty = Transcript::default();
+        assert_eq!(empty.text(), "");
+        assert_eq!(empty.to_srt(), "");
+        assert_eq!(empty.to_vtt(), "WEBVTT\n\n");
+
+        let json: serde_json::Value = serde_json::from_str(&empty.to_json()).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({ "language": null, "text": "", "segments": [] })
+        );
+    }
+
+    #[test]
+    fn json_has_the_joined_text_and_timed_segments() {
+        let mut transcript = transcript(vec![segment(0, 1000, "one"), segment(1000, 2000, "two")]);
+        transcript.segments[0].tokens.push(Token {
+            text: "one".to_string(),
+            probability: 0.5,
+        });
+
+        let json: serde_json::Value =
+            serde_json::from_str(&transcript.render(OutputFormat::Json)).unwrap();
+        assert_eq!(json["language"], "en");
+        assert_eq!(json["text"], "one two");
+        assert_eq!(json["segments"][1]["start_ms"], 1000);
+        assert_eq!(json["segments"][0]["tokens"][0]["text"], "one");
+        // Segments without tokens leave the key out
+        assert!(json["segments"][1].get("tokens").is_none());
+    }
+
+    #[test]
+    fn formats_from_extensions() {
+        assert_eq!(OutputFormat::from_extension("SRT"), Some(OutputFormat::Srt));
+        assert_eq!(OutputFormat::from_extension("vtt"), Some(OutputFormat::Vtt));
+        assert_eq!(
+            OutputFormat::from_extension("json"),
+            Some(OutputFormat::Json)
+        );
+        assert_eq!(OutputFormat::from_extension("txt"), Some(OutputFormat::Txt));
+        assert_eq!(OutputFormat::from_extension("mp3"), None);
+    }
+}
@@ -0,0 +1,154 @@
+//! `undo-last`: delete the last dictation by backspacing over it.
+//!
+//! The process exits after every dictation, so what was typed and where is
+//! taken from the history rather than from memory.
+
+use chrono::{DateTime, Local};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::history::{self, Entry};
+use crate::hyprland;
+use crate::inject::InjectorChain;
+use crate::settings::Settings;
+
+/// Backspace over the last dictation if it is recent and its window still
+/// has focus. Returns the number of characters deleted.
+pub fn undo_last(settings: &Settings) -> Result<usize, String> {
+    let entry = history::load()?
+        .pop()
+        .ok_or("Nothing to undo (is the history turned off?)")?;
+
+    let count = backspaces(&entry, settings.undo_max_age_secs, Local::now(), || {
+        hyprland::Client::from_env()
+            .and_then(|client| client.active_window())
+            .map(|window| window.map(|w| w.address))
+            .map_err(|e| e.to_string())
+    })?;
+    InjectorChain::from_names(&settings.text_injectors).edit(count, "")?;
+
+    history::update(entry.id, |entry| entry.undone = true)?;
+    Ok(count)
+}
+
+/// How many backspaces take `entry` back, or why it can't be undone.
+/// `active_window` is only asked once everything else checks out.
+fn backspaces(
+    entry: &Entry,
+    max_age_secs: u64,
+    now: DateTime<Local>,
+    active_window: impl FnOnce() -> Result<Option<String>, String>,
+) -> Result<usize, String> {
+    if entry.undone {
+        return Err("The last dictation was already undone".to_string());
+    }
+    if !entry.typed {
+        return Err("The last dictation wasn't typed, so there is nothing to delete".to_string());
+    }
+
+    let age = (now - entry.timestamp).num_seconds();
+    if age > max_age_secs as i64 {
+        return Err(format!(
+            "The last dictation is {}s old; undo only works for {}s",
+            age, max_age_secs
+        ));
+    }
+
+    // Backspacing into the wrong window would delete someone else's text
+    let address = entry
+        .window_address
+        .as_deref()
+        .ok_or("Don't know which window the last dictation went to")?;
+    if active_window()?.as_deref() != Some(address) {
+        return Err("Focus has moved since the last dictation".to_string());
+    }
+
+    // One backspace deletes one user-perceived character, e.g. a whole
+    // emoji or `e` plus a combining accent
+    Ok(entry.text.graphemes(true).count())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(text: &str) -> Entry {
+        Entry {
+            id: 1,
+            timestamp: Local::now(),
+            window_class: Some("kitty".to_string()),
+            window_address: Some("0xa".to_string()),
+            model: "ggml-base.bin".to_string(),
+            language: "en".to_string(),
+            duration_ms: 1000,
+            text: text.to_string(),
+            segments: Vec::new(),
+            error: None,
+            typed: true,
+            undone: false,
+        }
+    }
+
+    fn focused(address: &str) -> impl FnOnce() -> Result<Option<String>, String> + '_ {
+        move || Ok(Some(address.to_string()))
+    }
+
+    #[test]
+    fn counts_graphemes_not_chars() {
+        let check = |text: &str| backspaces(&entry(text), 60, Local::now(), focused("0xa"));
+        assert_eq!(check("hello"), Ok(5));
+        assert_eq!(check("cafe\u{301}"), Ok(4));
+        assert_eq!(check("ok 👍🏽"), Ok(4));
+        assert_eq!(check("👨‍👩‍👧"), Ok(1));
+        assert_eq!(check("a\r\nb"), Ok(3));
+    }
+
+    #[test]
+    fn refuses_when_another_window_has_focus() {
+        let entry = entry("hello");
+        assert_eq!(
+            backspaces(&entry, 60, Local::now(), focused("0xb")),
+            Err("Focus has moved since the last dictation".to_string())
+        );
+        assert_eq!(
+            backspaces(&entry, 60, Local::now(), || Ok(None)),
+            Err("Focus has moved since the last dictation".to_string())
+        );
+        assert_eq!(
+            backspaces(&entry, 60, Local::now(), || Err("no Hyprland".to_string())),
+            Err("no Hyprland".to_string())
+        );
+    }
+
+    #[test]
+    fn refuses_without_a_known_window() {
+        let mut entry = entry("hello");
+        entry.window_address = None;
+        assert_eq!(
+            backspaces(&entry, 60, Local::now(), || panic!("not asked")),
+            Err("Don't know which window the last dictation went to".to_string())
+        );
+    }
+
+    #[test]
+    fn refuses_old_untyped_or_undone_dictations() {
+        let not_asked = || -> Result<Option<String>, String> { panic!("not asked") };
+
+        let old = entry("hello");
+        let later = old.timestamp + chrono::Duration::seconds(61);
+        assert_eq!(
+            backspaces(&old, 60, later, not_asked),
+            Err("The last dictation is 61s old; undo only works for 60s".to_string())
+        );
+
+        let mut pasted = entry("hello");
+        pasted.typed = false;
+        assert!(backspaces(&pasted, 60, Local::now(), not_asked).is_err());
+
+        let mut undone = entry("hello");
+        undone.undone = true;
+        assert_eq!(
+            backspaces(&undone, 60, Local::now(), not_asked),
+            Err("The last dictation was already undone".to_string())
+        );
+    }
+}
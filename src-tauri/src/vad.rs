@@ -0,0 +1,271 @@
+use serde::Serialize;
+
+/// Tuning knobs for the energy + zero-crossing detector
+#[derive(Debug, Clone)]
+pub struct VadConfig {
+    /// Analysis frame length in milliseconds
+    pub frame_ms: u32,
+    /// Speech must be this many times louder than the tracked noise floor
+    pub energy_ratio: f32,
+    /// Absolute RMS below which a frame is never speech
+    pub min_energy: f32,
+    /// Frames above this zero-crossing rate need twice the energy to count
+    /// (hiss and fans cross zero a lot, voiced speech does not)
+    pub max_zcr: f32,
+    /// Consecutive speech frames required before speech-start fires
+    pub onset_frames: u32,
+    /// Consecutive silent frames required before speech-end fires
+    pub hangover_frames: u32,
+}
+
+impl Default for VadConfig {
+    fn default() -> Self {
+        Self {
+            frame_ms: 20,
+            energy_ratio: 3.0,
+            min_energy: 0.004,
+            max_zcr: 0.25,
+            onset_frames: 3,
+            hangover_frames: 15,
+        }
+    }
+}
+
+impl VadConfig {
+    /// Silence that has already passed when speech-end fires
+    pub fn hangover(&self) -> std::time::Duration {
+        std::time::Duration::from_millis((self.hangover_frames * self.frame_ms) as u64)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum VadEvent {
+    /// Speech began `at_ms` milliseconds into the recording
+    SpeechStart { at_ms: u64 },
+    /// Speech ended `at_ms` milliseconds into the recording
+    /// (already includes the hangover period)
+    SpeechEnd { at_ms: u64 },
+}
+
+/// Frame-based voice activity detector, run on the capture thread as samples
+/// come out of the ring buffer (see `audio::drain_ring`). Feed it mono
+/// samples as they arrive; it reports speech transitions.
+pub struct VoiceActivityDetector {
+    config: VadConfig,
+    sample_rate: u32,
+    frame_len: usize,
+    frame: Vec<f32>,
+    processed: u64,
+    noise_floor: f32,
+    speaking: bool,
+    speech_run: u32,
+    silence_run: u32,
+}
+
+impl VoiceActivityDetector {
+    pub fn new(sample_rate: u32, config: VadConfig) -> Self {
+        let frame_len = ((sample_rate as u64 * config.frame_ms as u64) / 1000).max(1) as usize;
+        Self {
+            config,
+            sample_rate,
+            frame_len,
+            frame: Vec::with_capacity(frame_len),
+            processed: 0,
+            noise_floor: 0.0,
+            speaking: false,
+            speech_run: 0,
+            silence_run: 0,
+        }
+    }
+
+    /// Process a block of mono samples, calling `on_event` for each transition
+    pub fn process(&mut self, samples: &[f32], mut on_event: impl FnMut(VadEvent)) {
+        for &sample in samples {
+            self.frame.push(sample);
+            if self.frame.len() == self.frame_len {
+                if let Some(event) = self.process_frame() {
+                    on_event(event);
+                }
+                self.frame.clear();
+            }
+        }
+    }
+
+    fn process_frame(&mut self) -> Option<VadEvent> {
+        self.processed += self.frame_len as u64;
+
+        let energy = rms(&self.frame);
+        let zcr = zero_crossing_rate(&self.frame);
+
+        // Seed the noise floor from the very first frame
+        if self.processed == self.frame_len as u64 {
+            self.noise_floor = energy;
+        }
+
+        let threshold = (self.noise_floor * self.config.energy_ratio).max(self.config.min_energy);
+        let is_speech = if zcr > self.config.max_zcr {
+            energy > threshold * 2.0
+        } else {
+            energy > threshold
+        };
+
+        if is_speech {
+            self.speech_run += 1;
+            self.silence_run = 0;
+        } else {
+            self.silence_run += 1;
+            self.speech_run = 0;
+            // Only adapt the floor on non-speech so it tracks the room, not the voice
+            self.noise_floor = self.noise_floor * 0.95 + energy * 0.05;
+        }
+
+        if !self.speaking && self.speech_run >= self.config.onset_frames {
+            self.speaking = true;
+            let onset = self.processed - (self.speech_run as u64 * self.frame_len as u64);
+            return Some(VadEvent::SpeechStart {
+                at_ms: self.samples_to_ms(onset),
+            });
+        }
+
+        if self.speaking && self.silence_run >= self.config.hangover_frames {
+            self.speaking = false;
+            return Some(VadEvent::SpeechEnd {
+                at_ms: self.samples_to_ms(self.processed),
+            });
+        }
+
+        None
+    }
+
+    fn samples_to_ms(&self, samples: u64) -> u64 {
+        samples * 1000 / self.sample_rate as u64
+    }
+}
+
+fn rms(frame: &[f32]) -> f32 {
+    if frame.is_empty() {
+        return 0.0;
+    }
+    let sum_squares: f32 = frame.iter().map(|s| s * s).sum();
+    (sum_squares / frame.len() as f32).sqrt()
+}
+
+fn zero_crossing_rate(frame: &[f32]) -> f32 {
+    if frame.len() < 2 {
+        return 0.0;
+    }
+    let crossings = frame
+        .windows(2)
+        .filter(|w| (w[0] >= 0.0) != (w[1] >= 0.0))
+        .count();
+    crossings as f32 / (frame.len() - 1) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 16000;
+
+    fn silence(ms: usize) -> Vec<f32> {
+        vec![0.0; ms * RATE as usize / 1000]
+    }
+
+    /// 200 Hz sine: voiced-speech-like energy with few zero crossings
+    fn tone(ms: usize, amplitude: f32) -> Vec<f32> {
+        (0..ms * RATE as usize / 1000)
+            .map(|i| amplitude * (i as f32 * 200.0 * std::f32::consts::TAU / RATE as f32).sin())
+            .collect()
+    }
+
+    /// Uniform white noise from a fixed seed, crossing zero about every other sample
+    fn noise(ms: usize, amplitude: f32) -> Vec<f32> {
+        let mut state = 0x2545_f491_u32;
+        (0..ms * RATE as usize / 1000)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                amplitude * (state as f32 / u32::MAX as f32 * 2.0 - 1.0)
+            })
+            .collect()
+    }
+
+    fn events(parts: &[Vec<f32>]) -> Vec<VadEvent> {
+        let mut vad = VoiceActivityDetector::new(RATE, VadConfig::default());
+        let mut events = Vec::new();
+        for part in parts {
+            vad.process(part, |event| events.push(event));
+        }
+        events
+    }
+
+    #[test]
+    fn silence_is_not_speech() {
+        assert_eq!(events(&[silence(2000)]), []);
+    }
+
+    #[test]
+    fn a_tone_starts_and_ends_speech() {
+        assert_eq!(
+            events(&[silence(500), tone(1000, 0.3), silence(1000)]),
+            [
+                VadEvent::SpeechStart { at_ms: 500 },
+                // The end is reported once the hangover has passed
+                VadEvent::SpeechEnd { at_ms: 1800 },
+            ]
+        );
+    }
+
+    #[test]
+    fn speech_end_waits_for_the_hangover() {
+        assert_eq!(
+            VadConfig::default().hangover(),
+            std::time::Duration::from_millis(300)
+        );
+
+        // A pause shorter than the hangover doesn't split the speech
+        assert_eq!(
+            events(&[
+                silence(200),
+                tone(500, 0.3),
+                silence(200),
+                tone(500, 0.3),
+                silence(1000)
+            ]),
+            [
+                VadEvent::SpeechStart { at_ms: 200 },
+                VadEvent::SpeechEnd { at_ms: 1700 },
+            ]
+        );
+
+        // Silence that ends just short of the hangover reports no end yet
+        assert_eq!(
+            events(&[silence(200), tone(500, 0.3), silence(280)]),
+            [VadEvent::SpeechStart { at_ms: 200 }]
+        );
+    }
+
+    #[test]
+    fn a_blip_shorter_than_the_onset_is_ignored() {
+        assert_eq!(events(&[silence(500), tone(40, 0.3), silence(500)]), []);
+    }
+
+    #[test]
+    fn noisy_frames_need_twice_the_energy() {
+        // Same loudness (RMS 0.006, above `min_energy` but below twice it):
+        // the tone counts as speech, the hiss doesn't
+        assert_eq!(
+            events(&[silence(200), tone(500, 0.006 * std::f32::consts::SQRT_2)]),
+            [VadEvent::SpeechStart { at_ms: 200 }]
+        );
+        assert_eq!(events(&[silence(200), noise(500, 0.006 * 3f32.sqrt())]), []);
+
+        // Loud enough hiss still gets through
+        assert_eq!(
+            events(&[silence(200), noise(500, 0.3)]),
+            [VadEvent::SpeechStart { at_ms: 200 }]
+        );
+    }
+}
@@ -0,0 +1,349 @@
+//! In-process text injection over `zwp_virtual_keyboard_v1`.
+//!
+//! Instead of spawning wtype for every delta, we keep one Wayland connection
+//! open, upload an XKB keymap that maps one keycode to each character (or key
+//! like BackSpace) we are about to send, and press those keycodes. Any Unicode
+//! character works, since the keymap is generated for the text rather than
+//! taken from the user's layout.
+
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::io::{Seek, Write};
+use std::os::fd::AsFd;
+use std::sync::Mutex;
+use std::time::Instant;
+use wayland_client::globals::{registry_queue_init, GlobalListContents};
+use wayland_client::protocol::{wl_keyboard, wl_registry, wl_seat};
+use wayland_client::{Connection, Dispatch, EventQueue, QueueHandle};
+use wayland_protocols_misc::zwp_virtual_keyboard_v1::client::{
+    zwp_virtual_keyboard_manager_v1::ZwpVirtualKeyboardManagerV1,
+    zwp_virtual_keyboard_v1::ZwpVirtualKeyboardV1,
+};
+
+use crate::inject::TextInjector;
+
+/// Distinct characters per keymap. Keycodes above 255 confuse X11 clients
+/// under Xwayland, so longer texts are typed in several keymap batches.
+const MAX_KEYS: usize = 200;
+/// XKB keycodes are evdev codes offset by 8; start past the reserved range
+const FIRST_KEYCODE: u32 = 9;
+
+const KEY_PRESSED: u32 = 1;
+const KEY_RELEASED: u32 = 0;
+
+/// Connection shared by every injection so typing a delta costs no handshake.
+/// Dropped after an error so the next call reconnects.
+static KEYBOARD: Lazy<Mutex<Option<VirtualKeyboard>>> = Lazy::new(|| Mutex::new(None));
+
+/// `TextInjector` backend using the shared virtual keyboard
+pub struct Native;
+
+impl TextInjector for Native {
+    fn name(&self) -> &'static str {
+        "native"
+    }
+
+    fn is_available(&self) -> bool {
+        std::env::var_os("WAYLAND_DISPLAY").is_some()
+    }
+
+    fn inject(&self, text: &str) -> Result<(), String> {
+        with_keyboard(|keyboard| keyboard.type_text(text))
+    }
+
+    fn backspace(&self, count: usize) -> Result<(), String> {
+        with_keyboard(|keyboard| keyboard.backspace(count))
+    }
+}
+
+/// Run `f` on the shared keyboard, connecting first if needed
+fn with_keyboard(f: impl FnOnce(&mut VirtualKeyboard) -> Result<(), String>) -> Result<(), String> {
+    let mut keyboard = KEYBOARD.lock().map_err(|e| e.to_string())?;
+
+    if keyboard.is_none() {
+        *keyboard = Some(VirtualKeyboard::connect()?);
+    }
+
+    let result = f(keyboard.as_mut().expect("keyboard was just connected"));
+    if result.is_err() {
+        *keyboard = None;
+    }
+    result
+}
+
+struct State;
+
+pub struct VirtualKeyboard {
+    queue: EventQueue<State>,
+    keyboard: ZwpVirtualKeyboardV1,
+    started: Instant,
+}
+
+impl VirtualKeyboard {
+    /// Connect to the compositor named by `WAYLAND_DISPLAY` and create a
+    /// virtual keyboard on its first seat
+    pub fn connect() -> Result<Self, String> {
+        let connection = Connection::connect_to_env()
+            .map_err(|e| format!("Failed to connect to Wayland: {}", e))?;
+        let (globals, queue) = registry_queue_init::<State>(&connection)
+            .map_err(|e| format!("Failed to list Wayland globals: {}", e))?;
+        let qh = queue.handle();
+
+        let seat: wl_seat::WlSeat = globals
+            .bind(&qh, 1..=7, ())
+            .map_err(|e| format!("No Wayland seat: {}", e))?;
+        let manager: ZwpVirtualKeyboardManagerV1 = globals
+            .bind(&qh, 1..=1, ())
+            .map_err(|e| format!("Compositor has no virtual keyboard support: {}", e))?;
+
+        let keyboard = manager.create_virtual_keyboard(&seat, &qh, ());
+
+        Ok(Self {
+            queue,
+            keyboard,
+            started: Instant::now(),
+        })
+    }
+
+    pub fn type_text(&mut self, text: &str) -> Result<(), String> {
+        let keys: Vec<String> = text.chars().filter_map(keysym_name).collect();
+        self.press(&keys)
+    }
+
+    pub fn backspace(&mut self, count: usize) -> Result<(), String> {
+        self.press(&vec!["BackSpace".to_string(); count])
+    }
+
+    /// Press and release each keysym in turn
+    fn press(&mut self, keys: &[String]) -> Result<(), String> {
+        for batch in batches(keys) {
+            let keycodes = self.upload_keymap(&batch)?;
+
+            for key in &batch {
+                let key = keycodes[key] - 8;
+                self.keyboard.key(self.time(), key, KEY_PRESSED);
+                self.keyboard.key(self.time(), key, KEY_RELEASED);
+            }
+
+            // Make sure the compositor has handled the keys before the next
+            // keymap replaces the one they refer to
+            self.queue
+                .roundtrip(&mut State)
+                .map_err(|e| format!("Virtual keyboard error: {}", e))?;
+        }
+
+        Ok(())
+    }
+
+    /// Upload a keymap covering `keys` and return each keysym's keycode
+    fn upload_keymap(&mut self, keys: &[String]) -> Result<HashMap<String, u32>, String> {
+        let mut keycodes = HashMap::new();
+        for key in keys {
+            let next = FIRST_KEYCODE + keycodes.len() as u32;
+            keycodes.entry(key.clone()).or_insert(next);
+        }
+
+        let mut keymap = build_keymap(&keycodes).into_bytes();
+        keymap.push(0);
+
+        let mut file =
+            tempfile::tempfile().map_err(|e| format!("Failed to create keymap file: {}", e))?;
+        file.write_all(&keymap)
+            .and_then(|_| file.flush())
+            .and_then(|_| file.rewind())
+            .map_err(|e| format!("Failed to write keymap: {}", e))?;
+
+        self.keyboard.keymap(
+            wl_keyboard::KeymapFormat::XkbV1 as u32,
+            file.as_fd(),
+            keymap.len() as u32,
+        );
+        self.keyboard.modifiers(0, 0, 0, 0);
+
+        Ok(keycodes)
+    }
+
+    fn time(&self) -> u32 {
+        self.started.elapsed().as_millis() as u32
+    }
+}
+
+/// Split `keys` into runs with at most `MAX_KEYS` distinct keysyms each
+fn batches(keys: &[String]) -> Vec<Vec<String>> {
+    let mut batches = Vec::new();
+    let mut current = Vec::new();
+    let mut distinct = std::collections::HashSet::new();
+
+    for key in keys {
+        if !distinct.contains(key) && distinct.len() == MAX_KEYS {
+            batches.push(std::mem::take(&mut current));
+            distinct.clear();
+        }
+        distinct.insert(key.clone());
+        current.push(key.clone());
+    }
+    if !current.is_empty() {
+        batches.push(current);
+    }
+    batches
+}
+
+/// XKB keysym name for a character, `None` for control characters we can't type
+fn keysym_name(c: char) -> Option<String> {
+    match c {
+        '\n' => Some("Return".to_string()),
+        '\t' => Some("Tab".to_string()),
+        c if c.is_control() => None,
+        // `Uxxxx` resolves to the legacy keysym for Latin-1 and to a
+        // Unicode keysym for everything else
+        c => Some(format!("U{:04X}", c as u32)),
+    }
+}
+
+/// A complete XKB keymap with one single-level key per keysym
+fn build_keymap(keycodes: &HashMap<String, u32>) -> String {
+    let mut keys: Vec<(&String, &u32)> = keycodes.iter().collect();
+    keys.sort_by_key(|(_, code)| **code);
+
+    let max = keys.last().map(|(_, code)| **code).unwrap_or(FIRST_KEYCODE);
+
+    let mut keymap = String::from("xkb_keymap {\n");
+    keymap.push_str(&format!(
+        "xkb_keycodes \"hyprwhisper\" {{\nminimum = 8;\nmaximum = {};\n",
+        max + 1
+    ));
+    for (_, code) in &keys {
+        keymap.push_str(&format!("<K{}> = {};\n", code, code));
+    }
+    keymap.push_str("};\n");
+    keymap.push_str("xkb_types \"hyprwhisper\" { include \"complete\" };\n");
+    keymap.push_str("xkb_compatibility \"hyprwhisper\" { include \"complete\" };\n");
+    keymap.push_str("xkb_symbols \"hyprwhisper\" {\n");
+    for (name, code) in &keys {
+        keymap.push_str(&format!("key <K{}> {{[ {} ]}};\n", code, name));
+    }
+    keymap.push_str("};\n};\n");
+    keymap
+}
+
+impl Dispatch<wl_registry::WlRegistry, GlobalListContents> for State {
+    fn event(
+        _: &mut Self,
+        _: &wl_registry::WlRegistry,
+        _: wl_registry::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<wl_seat::WlSeat, ()> for State {
+    fn event(
+        _: &mut Self,
+        _: &wl_seat::WlSeat,
+        _: wl_seat::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ZwpVirtualKeyboardManagerV1, ()> for State {
+    fn event(
+        _: &mut Self,
+        _: &ZwpVirtualKeyboardManagerV1,
+        _: <ZwpVirtualKeyboardManagerV1 as wayland_client::Proxy>::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ZwpVirtualKeyboardV1, ()> for State {
+    fn event(
+        _: &mut Self,
+        _: &ZwpVirtualKeyboardV1,
+        _: <ZwpVirtualKeyboardV1 as wayland_client::Proxy>::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn batches_split_after_max_distinct_keys() {
+        let distinct: Vec<String> = (0..MAX_KEYS + 50).map(|i| format!("K{}", i)).collect();
+        let split = batches(&distinct);
+        assert_eq!(split.len(), 2);
+        assert_eq!(split[0].len(), MAX_KEYS);
+        assert_eq!(split[1].len(), 50);
+        assert_eq!(split.concat(), distinct);
+    }
+
+    #[test]
+    fn batches_count_repeated_keys_once() {
+        let mut input: Vec<String> = (0..MAX_KEYS).map(|i| format!("K{}", i)).collect();
+        // Already in the first keymap, so no new batch yet
+        input.push("K0".to_string());
+        input.push("K1".to_string());
+        input.push("new".to_string());
+
+        let split = batches(&input);
+        assert_eq!(split.len(), 2);
+        assert_eq!(split[0].len(), MAX_KEYS + 2);
+        assert_eq!(split[1], keys(&["new"]));
+
+        assert_eq!(
+            batches(&keys(&["a", "b", "a", "a"])),
+            [keys(&["a", "b", "a", "a"])]
+        );
+        assert!(batches(&[]).is_empty());
+    }
+
+    #[test]
+    fn keysym_names() {
+        assert_eq!(keysym_name('\n').as_deref(), Some("Return"));
+        assert_eq!(keysym_name('\t').as_deref(), Some("Tab"));
+        assert_eq!(keysym_name('\u{7}'), None);
+        assert_eq!(keysym_name('\r'), None);
+        assert_eq!(keysym_name('a').as_deref(), Some("U0061"));
+        assert_eq!(keysym_name('é').as_deref(), Some("U00E9"));
+        assert_eq!(keysym_name('😀').as_deref(), Some("U1F600"));
+    }
+
+    #[test]
+    fn keymap_declares_every_keycode() {
+        let keycodes = HashMap::from([
+            ("U0061".to_string(), FIRST_KEYCODE),
+            ("BackSpace".to_string(), FIRST_KEYCODE + 1),
+            ("U1F600".to_string(), FIRST_KEYCODE + 2),
+        ]);
+        let keymap = build_keymap(&keycodes);
+
+        assert!(keymap.contains("minimum = 8;"));
+        assert!(keymap.contains(&format!("maximum = {};", FIRST_KEYCODE + 3)));
+        assert!(keymap.contains("<K9> = 9;\n<K10> = 10;\n<K11> = 11;\n"));
+        assert!(keymap.contains("key <K9> {[ U0061 ]};"));
+        assert!(keymap.contains("key <K10> {[ BackSpace ]};"));
+        assert!(keymap.contains("key <K11> {[ U1F600 ]};"));
+    }
+
+    #[test]
+    fn empty_keymap_is_still_valid() {
+        let keymap = build_keymap(&HashMap::new());
+        assert!(keymap.contains(&format!("maximum = {};", FIRST_KEYCODE + 1)));
+        assert!(keymap.ends_with("};\n};\n"));
+    }
+}
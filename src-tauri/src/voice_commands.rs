@@ -0,0 +1,375 @@
+//! Spoken formatting commands ("new line", "comma", "scratch that", ...).
+//!
+//! `VoiceCommands::render` turns the committed transcript into the text that
+//! should be in the target window. It is a pure function of the transcript,
+//! so re-rendering after every commit and diffing against what was typed
+//! yields the keystrokes to send, including deletions.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Command {
+    NewLine,
+    NewParagraph,
+    /// Attached to the previous word without a space, e.g. `"."`
+    Punctuation(String),
+    /// Typed as a word of its own, e.g. `"@"` or a signature
+    Text(String),
+    /// Remove the sentence being dictated, or the previous one if the
+    /// current one is empty
+    DeleteLastSentence,
+    CapsOn,
+    CapsOff,
+}
+
+/// A user-defined phrase, e.g. `{ phrase = "smiley", command = { text = ":)" } }`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CustomCommand {
+    pub phrase: String,
+    pub command: Command,
+}
+
+pub struct VoiceCommands {
+    /// Phrases as normalized words, longest first so "new paragraph" wins
+    /// over a shorter phrase sharing its start
+    phrases: Vec<(Vec<String>, Command)>,
+}
+
+impl VoiceCommands {
+    /// The built-in table for `language` (English for "auto" or unknown
+    /// languages) plus `custom`, which takes precedence
+    pub fn new(language: &str, custom: &[CustomCommand]) -> Self {
+        let builtin = builtin_table(language)
+            .into_iter()
+            .map(|(phrase, command)| (phrase.to_string(), command));
+        let custom = custom.iter().map(|c| (c.phrase.clone(), c.command.clone()));
+
+        let mut phrases: Vec<(Vec<String>, Command)> = custom
+            .chain(builtin)
+            .map(|(phrase, command)| {
+                let words: Vec<String> = phrase.split_whitespace().map(normalize).collect();
+                (words, command)
+            })
+            .filter(|(words, _)| !words.is_empty())
+            .collect();
+        // Stable, so custom phrases still come before built-ins of equal length
+        phrases.sort_by_key(|(words, _)| std::cmp::Reverse(words.len()));
+
+        Self { phrases }
+    }
+
+    /// Commands off: text passes through unchanged
+    pub fn disabled() -> Self {
+        Self {
+            phrases: Vec::new(),
+        }
+    }
+
+    /// Apply the commands in `transcript` and return the resulting text
+    pub fn render(&self, transcript: &str) -> String {
+        let words: Vec<&str> = transcript.split_whitespace().collect();
+        let normalized: Vec<String> = words.iter().map(|w| normalize(w)).collect();
+
+        let mut out = String::new();
+        let mut caps = false;
+        let mut i = 0;
+
+        while i < words.len() {
+            match self.match_at(&normalized[i..]) {
+                Some((len, command)) => {
+                    apply(&mut out, &mut caps, command);
+                    i += len;
+                }
+                None => {
+                    let word = if caps {
+                        words[i].to_uppercase()
+                    } else {
+                        words[i].to_string()
+                    };
+                    push_word(&mut out, &word);
+                    i += 1;
+                }
+            }
+        }
+
+        out
+    }
+
+    fn match_at(&self, words: &[String]) -> Option<(usize, &Command)> {
+        self.phrases
+            .iter()
+            .find(|(phrase, _)| words.starts_with(phrase))
+            .map(|(phrase, command)| (phrase.len(), command))
+    }
+}
+
+fn apply(out: &mut String, caps: &mut bool, command: &Command) {
+    match command {
+        Command::NewLine => {
+            trim_spaces(out);
+            out.push('\n');
+        }
+        Command::NewParagraph => {
+            trim_spaces(out);
+            out.push_str("\n\n");
+        }
+        Command::Punctuation(mark) => {
+            trim_spaces(out);
+            // Whisper often punctuates on its own ("Hello. Period."); the
+            // spoken mark replaces its guess
+            if out.ends_with(is_punctuation) {
+                out.pop();
+            }
+            out.push_str(mark);
+        }
+        Command::Text(text) => push_word(out, text),
+        Command::DeleteLastSentence => delete_last_sentence(out),
+        Command::CapsOn => *caps = true,
+        Command::CapsOff => *caps = false,
+    }
+}
+
+fn push_word(out: &mut String, word: &str) {
+    if !out.is_empty() && !out.ends_with(char::is_whitespace) {
+        out.push(' ');
+    }
+    out.push_str(word);
+}
+
+/// Drop trailing spaces (not newlines)
+fn trim_spaces(out: &mut String) {
+    let len = out.trim_end_matches(' ').len();
+    out.truncate(len);
+}
+
+fn delete_last_sentence(out: &mut String) {
+    let len = out.trim_end().len();
+    out.truncate(len);
+
+    // The mark ending the sentence we delete belongs to it
+    let body = out.trim_end_matches(is_sentence_mark);
+    let cut = body
+        .rfind(|c: char| is_sentence_mark(c) || c == '\n')
+        .map(|i| i + 1)
+        .unwrap_or(0);
+    out.truncate(cut);
+}
+
+fn is_punctuation(c: char) -> bool {
+    matches!(c, '.' | ',' | '!' | '?' | ';' | ':')
+}
+
+fn is_sentence_mark(c: char) -> bool {
+    matches!(c, '.' | '!' | '?')
+}
+
+/// Lowercase and strip the punctuation Whisper attaches to words
+fn normalize(word: &str) -> String {
+    word.trim_matches(|c: char| !c.is_alphanumeric())
+        .to_lowercase()
+}
+
+fn builtin_table(language: &str) -> Vec<(&'static str, Command)> {
+    use Command::*;
+    let p = |mark: &str| Punctuation(mark.to_string());
+
+    match language {
+        "de" => vec![
+            ("neue zeile", NewLine),
+            ("neuer absatz", NewParagraph),
+            ("punkt", p(".")),
+            ("komma", p(",")),
+            ("fragezeichen", p("?")),
+            ("ausrufezeichen", p("!")),
+            ("doppelpunkt", p(":")),
+            ("semikolon", p(";")),
+            ("das löschen", DeleteLastSentence),
+            ("letzten satz löschen", DeleteLastSentence),
+            ("großbuchstaben an", CapsOn),
+            ("großbuchstaben aus", CapsOff),
+        ],
+        "es" => vec![
+            ("nueva línea", NewLine),
+            ("nuevo párrafo", NewParagraph),
+            ("punto", p(".")),
+            ("coma", p(",")),
+            ("signo de interrogación", p("?")),
+            ("signo de exclamación", p("!")),
+            ("dos puntos", p(":")),
+            ("punto y coma", p(";")),
+            ("borra eso", DeleteLastSentence),
+            ("borrar última frase", DeleteLastSentence),
+            ("mayúsculas activadas", CapsOn),
+            ("mayúsculas desactivadas", CapsOff),
+        ],
+        "fr" => vec![
+            ("nouvelle ligne", NewLine),
+            ("nouveau paragraphe", NewParagraph),
+            ("point", p(".")),
+            ("virgule", p(",")),
+            ("point d'interrogation", p("?")),
+            ("point d'exclamation", p("!")),
+            ("deux points", p(":")),
+            ("point virgule", p(";")),
+            ("efface ça", DeleteLastSentence),
+            ("supprimer la dernière phrase", DeleteLastSentence),
+            ("majuscules activées", CapsOn),
+            ("majuscules désactivées", CapsOff),
+        ],
+        _ => vec![
+            ("new line", NewLine),
+            ("newline", NewLine),
+            ("new paragraph", NewParagraph),
+            ("period", p(".")),
+            ("full stop", p(".")),
+            ("comma", p(",")),
+            ("question mark", p("?")),
+            ("exclamation mark", p("!")),
+            ("exclamation point", p("!")),
+            ("colon", p(":")),
+            ("semicolon", p(";")),
+            ("scratch that", DeleteLastSentence),
+            ("delete that", DeleteLastSentence),
+            ("delete last sentence", DeleteLastSentence),
+            ("caps on", CapsOn),
+            ("caps off", CapsOff),
+        ],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(language: &str, transcript: &str) -> String {
+        VoiceCommands::new(language, &[]).render(transcript)
+    }
+
+    #[test]
+    fn spoken_punctuation_replaces_whispers_own() {
+        assert_eq!(render("en", "Hello. Period."), "Hello.");
+        assert_eq!(render("en", "Really, question mark"), "Really?");
+        assert_eq!(render("en", "Hello comma world full stop"), "Hello, world.");
+    }
+
+    #[test]
+    fn new_line_and_paragraph_drop_the_space_before_them() {
+        assert_eq!(
+            render("en", "first line new line second line"),
+            "first line\nsecond line"
+        );
+        assert_eq!(render("en", "One. New paragraph. Two."), "One.\n\nTwo.");
+    }
+
+    #[test]
+    fn scratch_that_deletes_the_current_sentence() {
+        assert_eq!(
+            render("en", "First sentence. Second part scratch that"),
+            "First sentence."
+        );
+        assert_eq!(
+            render("en", "First sentence. Second part scratch that. Third."),
+            "First sentence. Third."
+        );
+        assert_eq!(render("en", "Hello there, scratch that."), "");
+    }
+
+    #[test]
+    fn scratch_that_on_an_empty_sentence_deletes_the_previous_one() {
+        assert_eq!(render("en", "One. Two. Scratch that."), "One.");
+        assert_eq!(render("en", "One. New line Two. Scratch that"), "One.\n");
+        assert_eq!(render("en", "Scratch that"), "");
+    }
+
+    #[test]
+    fn caps_on_and_off() {
+        assert_eq!(
+            render("en", "say caps on hello world caps off bye"),
+            "say HELLO WORLD bye"
+        );
+    }
+
+    #[test]
+    fn longest_phrase_wins() {
+        assert_eq!(render("es", "hola punto y coma adiós"), "hola; adiós");
+        assert_eq!(render("es", "hola punto"), "hola.");
+        assert_eq!(render("fr", "Bonjour point d'exclamation"), "Bonjour!");
+    }
+
+    #[test]
+    fn custom_commands_override_builtins() {
+        let custom = [
+            CustomCommand {
+                phrase: "period".to_string(),
+                command: Command::Text("period".to_string()),
+            },
+            CustomCommand {
+                phrase: "smiley face".to_string(),
+                command: Command::Text(":)".to_string()),
+            },
+        ];
+        let commands = VoiceCommands::new("en", &custom);
+        assert_eq!(
+            commands.render("a long period, smiley face"),
+            "a long period :)"
+        );
+        assert_eq!(commands.render("done comma"), "done,");
+    }
+
+    #[test]
+    fn german_table() {
+        assert_eq!(
+            render(
+                "de",
+                "Hallo Komma Welt Punkt neue Zeile Tschüss Ausrufezeichen"
+            ),
+            "Hallo, Welt.\nTschüss!"
+        );
+        assert_eq!(
+            render("de", "Erster Satz. Zweiter das löschen"),
+            "Erster Satz."
+        );
+    }
+
+    #[test]
+    fn spanish_table() {
+        assert_eq!(
+            render(
+                "es",
+                "Hola coma qué tal signo de interrogación nuevo párrafo Adiós"
+            ),
+            "Hola, qué tal?\n\nAdiós"
+        );
+        assert_eq!(
+            render("es", "mayúsculas activadas hola mayúsculas desactivadas"),
+            "HOLA"
+        );
+    }
+
+    #[test]
+    fn french_table() {
+        assert_eq!(
+            render(
+                "fr",
+                "Bonjour virgule monde point d'interrogation nouvelle ligne Salut point"
+            ),
+            "Bonjour, monde?\nSalut."
+        );
+        assert_eq!(render("fr", "Une phrase. Efface ça."), "");
+    }
+
+    #[test]
+    fn unknown_languages_use_english() {
+        assert_eq!(render("auto", "hi comma there"), "hi, there");
+        assert_eq!(render("en", "hola coma"), "hola coma");
+    }
+
+    #[test]
+    fn disabled_passes_words_through() {
+        assert_eq!(
+            VoiceCommands::disabled().render("new line  period"),
+            "new line period"
+        );
+    }
+}
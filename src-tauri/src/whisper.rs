@@ -1,6 +1,14 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
-use whisper_rs::{FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters, WhisperState};
+use whisper_rs::{
+    FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters, WhisperState,
+};
+
+use crate::resample::Resampler;
+use crate::transcript::{Segment, Token, Transcript};
+
+/// Whisper's native input rate
+pub const WHISPER_SAMPLE_RATE: u32 = 16000;
 
 pub struct WhisperEngine {
     context: Option<WhisperContext>,
@@ -21,12 +29,10 @@ impl WhisperEngine {
         }
 
         let params = WhisperContextParameters::default();
-        
-        let ctx = WhisperContext::new_with_params(
-            model_path.to_str().ok_or("Invalid path")?,
-            params,
-        )
-        .map_err(|e| format!("Failed to load Whisper model: {}", e))?;
+
+        let ctx =
+            WhisperContext::new_with_params(model_path.to_str().ok_or("Invalid path")?, params)
+                .map_err(|e| format!("Failed to load Whisper model: {}", e))?;
 
         self.context = Some(ctx);
         self.model_path = Some(model_path);
@@ -41,7 +47,26 @@ impl WhisperEngine {
             .map_err(|e| format!("Failed to create state: {}", e))
     }
 
-    pub fn transcribe(&self, audio_samples: &[f32], language: Option<&str>) -> Result<String, String> {
+    pub fn transcribe(
+        &self,
+        audio_samples: &[f32],
+        language: Option<&str>,
+        prompt: &str,
+    ) -> Result<String, String> {
+        Ok(self
+            .transcribe_detailed(audio_samples, language, prompt)?
+            .text())
+    }
+
+    /// Transcribe audio keeping segment timings, token probabilities and the
+    /// language that was used or detected. `prompt` biases the decoder
+    /// towards its spelling and style (see `Settings::whisper_prompt`).
+    pub fn transcribe_detailed(
+        &self,
+        audio_samples: &[f32],
+        language: Option<&str>,
+        prompt: &str,
+    ) -> Result<Transcript, String> {
         let ctx = self.context.as_ref().ok_or("Model not loaded")?;
 
         let mut state = ctx
@@ -49,7 +74,7 @@ impl WhisperEngine {
             .map_err(|e| format!("Failed to create state: {}", e))?;
 
         let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
-        
+
         // Configure for best results
         params.set_print_special(false);
         params.set_print_progress(false);
@@ -58,7 +83,7 @@ impl WhisperEngine {
         params.set_translate(false);
         params.set_single_segment(false);
         params.set_no_context(true);
-        
+
         // Set language if specified
         if let Some(lang) = language {
             params.set_language(Some(lang));
@@ -67,30 +92,39 @@ impl WhisperEngine {
             params.set_language(None);
         }
 
+        if !prompt.is_empty() {
+            params.set_initial_prompt(prompt);
+        }
+
         // Run inference
         state
             .full(params, audio_samples)
             .map_err(|e| format!("Transcription failed: {}", e))?;
 
-        // Collect results
-        let num_segments = state
-            .full_n_segments()
-            .map_err(|e| format!("Failed to get segments: {}", e))?;
-
-        let mut result = String::new();
-        for i in 0..num_segments {
-            let segment = state
-                .full_get_segment_text(i)
-                .map_err(|e| format!("Failed to get segment {}: {}", i, e))?;
-            result.push_str(&segment);
-        }
+        let language = match language {
+            Some(lang) => Some(lang.to_string()),
+            None => state
+                .full_lang_id_from_state()
+                .ok()
+                .and_then(whisper_rs::get_lang_str)
+                .map(str::to_string),
+        };
 
-        Ok(result.trim().to_string())
+        Ok(Transcript {
+            language,
+            segments: collect_segments(ctx, &state)?,
+        })
     }
 
-    /// Transcribe a chunk of audio for real-time streaming
-    /// Uses shorter audio segments for faster response
-    pub fn transcribe_chunk(&self, audio_samples: &[f32], language: Option<&str>) -> Result<String, String> {
+    /// Transcribe a window of live audio, keeping segment timings so the
+    /// caller can decide which part of the window is safe to commit.
+    /// `prompt` is fed to the decoder as previous context.
+    pub fn transcribe_window(
+        &self,
+        audio_samples: &[f32],
+        language: Option<&str>,
+        prompt: &str,
+    ) -> Result<Vec<Segment>, String> {
         let ctx = self.context.as_ref().ok_or("Model not loaded")?;
 
         let mut state = ctx
@@ -98,22 +132,20 @@ impl WhisperEngine {
             .map_err(|e| format!("Failed to create state: {}", e))?;
 
         let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
-        
+
         // Optimize for real-time streaming
         params.set_print_special(false);
         params.set_print_progress(false);
         params.set_print_realtime(false);
         params.set_print_timestamps(false);
         params.set_translate(false);
-        params.set_single_segment(true); // Single segment for speed
+        params.set_single_segment(false); // Need segment boundaries to commit on
         params.set_no_context(true);
         params.set_n_threads(4); // Use multiple threads
-        
-        // Set language - using specific language is faster than auto-detect
-        if let Some(lang) = language {
-            params.set_language(Some(lang));
-        } else {
-            params.set_language(Some("en")); // Default to English for speed
+        params.set_language(language);
+
+        if !prompt.is_empty() {
+            params.set_initial_prompt(prompt);
         }
 
         // Run inference
@@ -121,20 +153,7 @@ impl WhisperEngine {
             .full(params, audio_samples)
             .map_err(|e| format!("Transcription failed: {}", e))?;
 
-        // Collect results
-        let num_segments = state
-            .full_n_segments()
-            .map_err(|e| format!("Failed to get segments: {}", e))?;
-
-        let mut result = String::new();
-        for i in 0..num_segments {
-            let segment = state
-                .full_get_segment_text(i)
-                .map_err(|e| format!("Failed to get segment {}: {}", i, e))?;
-            result.push_str(&segment);
-        }
-
-        Ok(result.trim().to_string())
+        collect_segments(ctx, &state)
     }
 
     pub fn is_loaded(&self) -> bool {
@@ -152,6 +171,320 @@ impl Default for WhisperEngine {
     }
 }
 
+/// Read the segments of a finished run, skipping special tokens
+fn collect_segments(ctx: &WhisperContext, state: &WhisperState) -> Result<Vec<Segment>, String> {
+    let num_segments = state
+        .full_n_segments()
+        .map_err(|e| format!("Failed to get segments: {}", e))?;
+
+    let mut segments = Vec::with_capacity(num_segments.max(0) as usize);
+    for i in 0..num_segments {
+        let text = state
+            .full_get_segment_text_lossy(i)
+            .map_err(|e| format!("Failed to get segment {}: {}", i, e))?;
+        // Whisper timestamps are in centiseconds
+        let t0 = state
+            .full_get_segment_t0(i)
+            .map_err(|e| format!("Failed to get segment {} start: {}", i, e))?;
+        let t1 = state
+            .full_get_segment_t1(i)
+            .map_err(|e| format!("Failed to get segment {} end: {}", i, e))?;
+
+        let num_tokens = state
+            .full_n_tokens(i)
+            .map_err(|e| format!("Failed to get tokens of segment {}: {}", i, e))?;
+
+        let mut tokens = Vec::new();
+        for j in 0..num_tokens {
+            let id = state
+                .full_get_token_id(i, j)
+                .map_err(|e| format!("Failed to get token {}/{}: {}", i, j, e))?;
+            // [_BEG_], [_TT_123] and friends all sort after end-of-text
+            if id >= ctx.token_eot() {
+                continue;
+            }
+            tokens.push(Token {
+                text: state
+                    .full_get_token_text_lossy(i, j)
+                    .map_err(|e| format!("Failed to get token {}/{}: {}", i, j, e))?,
+                probability: state
+                    .full_get_token_prob(i, j)
+                    .map_err(|e| format!("Failed to get token {}/{}: {}", i, j, e))?,
+            });
+        }
+
+        let probability = if tokens.is_empty() {
+            0.0
+        } else {
+            tokens.iter().map(|t| t.probability).sum::<f32>() / tokens.len() as f32
+        };
+
+        segments.push(Segment {
+            start_ms: t0 * 10,
+            end_ms: t1 * 10,
+            text: text.trim().to_string(),
+            probability,
+            tokens,
+        });
+    }
+
+    Ok(segments)
+}
+
+// ===== Streaming transcription =====
+
+/// Once the window holds this much audio, everything but its last segment is committed
+const COMMIT_WINDOW_MS: usize = 10_000;
+/// Hard cap on the window; at this length even the last segment is committed
+const MAX_WINDOW_MS: usize = 25_000;
+/// Audio kept in front of a commit point so the next window has acoustic context
+const OVERLAP_MS: usize = 1_000;
+/// Don't bother running Whisper on less audio than this
+const MIN_WINDOW_MS: usize = 500;
+/// How much committed text is passed back in as the decoder prompt
+const PROMPT_CHARS: usize = 200;
+/// Longest run of repeated words looked for when stitching across the overlap
+const MAX_OVERLAP_WORDS: usize = 8;
+
+fn ms_to_samples(ms: usize) -> usize {
+    ms * WHISPER_SAMPLE_RATE as usize / 1000
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct StreamingUpdate {
+    /// Text that will not change for the rest of the session
+    pub committed: String,
+    /// Transcription of the current window, may still change
+    pub tentative: String,
+}
+
+impl StreamingUpdate {
+    pub fn text(&self) -> String {
+        join_text(&self.committed, &self.tentative)
+    }
+}
+
+/// Incremental transcriber for live dictation.
+///
+/// Only new audio is pulled from the recorder. Whisper runs over a bounded
+/// window of uncommitted audio; when the window grows long, its finished
+/// segments are committed and dropped (minus a short overlap), and the
+/// committed text is fed back as the prompt. Cost per step therefore stays
+/// flat no matter how long the session runs.
+pub struct StreamingTranscriber {
+    /// Carries filter state across chunks, so the window matches what
+    /// resampling the whole recording gives
+    resampler: Resampler,
+    /// User vocabulary and prompt, placed before the committed text
+    initial_prompt: String,
+    consumed: usize,
+    window: Vec<f32>,
+    /// 16kHz samples already dropped from the front of the window
+    dropped: usize,
+    committed: String,
+    /// The committed text's segments, timed from the start of the session
+    segments: Vec<Segment>,
+    tentative: String,
+}
+
+impl StreamingTranscriber {
+    pub fn new(source_rate: u32, initial_prompt: String) -> Self {
+        Self {
+            resampler: Resampler::new(source_rate, WHISPER_SAMPLE_RATE),
+            initial_prompt,
+            consumed: 0,
+            window: Vec::new(),
+            dropped: 0,
+            committed: String::new(),
+            segments: Vec::new(),
+            tentative: String::new(),
+        }
+    }
+
+    /// Number of recorder samples (at the source rate) already pulled in
+    pub fn consumed(&self) -> usize {
+        self.consumed
+    }
+
+    /// Position in the session's 16kHz audio up to which the window has
+    /// been filled. The resampler holds back about a millisecond, so this
+    /// trails `consumed()` slightly.
+    pub fn consumed_resampled(&self) -> usize {
+        self.resampler.produced()
+    }
+
+    /// Append newly recorded samples (at the source rate)
+    pub fn push(&mut self, samples: &[f32]) {
+        self.consumed += samples.len();
+        self.window.extend(self.resampler.process(samples));
+    }
+
+    /// Re-transcribe the current window, committing finished segments once it
+    /// grows past `COMMIT_WINDOW_MS`
+    pub fn step(
+        &mut self,
+        engine: &WhisperEngine,
+        language: Option<&str>,
+    ) -> Result<StreamingUpdate, String> {
+        if self.window.len() < ms_to_samples(MIN_WINDOW_MS) {
+            return Ok(self.update());
+        }
+
+        let segments = engine.transcribe_window(&self.window, language, &self.prompt())?;
+        self.absorb(&segments);
+        Ok(self.update())
+    }
+
+    /// Fold a transcription of the current window into the committed and
+    /// tentative text, dropping committed audio from the window
+    fn absorb(&mut self, segments: &[Segment]) {
+        let commit_count = if self.window.len() >= ms_to_samples(MAX_WINDOW_MS) {
+            segments.len()
+        } else if self.window.len() >= ms_to_samples(COMMIT_WINDOW_MS) {
+            segments.len().saturating_sub(1)
+        } else {
+            0
+        };
+
+        if commit_count > 0 {
+            self.commit(&segments[..commit_count]);
+
+            let cut_ms = segments[commit_count - 1].end_ms.max(0) as usize;
+            let cut = ms_to_samples(cut_ms.saturating_sub(OVERLAP_MS)).min(self.window.len());
+            self.window.drain(..cut);
+            self.dropped += cut;
+        } else if self.window.len() >= ms_to_samples(MAX_WINDOW_MS) {
+            // A full window with nothing to commit is silence or noise; drop it
+            let cut = self.window.len() - ms_to_samples(OVERLAP_MS);
+            self.window.drain(..cut);
+            self.dropped += cut;
+        }
+
+        let tentative = join_segments(&segments[commit_count..]);
+        self.tentative = strip_overlap(&self.committed, &tentative);
+    }
+
+    /// Transcribe whatever is left and commit all of it. `tail` is the 16kHz
+    /// audio recorded after `consumed_resampled()`. The transcript's text is
+    /// everything committed during the session.
+    pub fn finish(
+        &mut self,
+        engine: &WhisperEngine,
+        tail: &[f32],
+        language: Option<&str>,
+    ) -> Result<Transcript, String> {
+        self.window.extend_from_slice(tail);
+
+        if !self.window.is_empty() {
+            let segments = engine.transcribe_window(&self.window, language, &self.prompt())?;
+            self.commit(&segments);
+            self.dropped += self.window.len();
+            self.window.clear();
+        }
+
+        self.tentative.clear();
+        Ok(Transcript {
+            language: language.map(str::to_string),
+            segments: self.segments.clone(),
+        })
+    }
+
+    /// Commit `segments` of the current window, minus the words that repeat
+    /// the end of what was committed before
+    fn commit(&mut self, segments: &[Segment]) {
+        let text = join_segments(segments);
+        let stripped = strip_overlap(&self.committed, &text);
+        let mut repeated = text.split_whitespace().count() - stripped.split_whitespace().count();
+        let offset_ms = (self.dropped * 1000 / WHISPER_SAMPLE_RATE as usize) as i64;
+
+        for segment in segments {
+            let words: Vec<&str> = segment.text.split_whitespace().collect();
+            let skip = repeated.min(words.len());
+            repeated -= skip;
+            if skip == words.len() {
+                continue;
+            }
+
+            let mut segment = segment.clone();
+            if skip > 0 {
+                // The tokens still spell the dropped words
+                segment.tokens.clear();
+            }
+            segment.text = words[skip..].join(" ");
+            segment.start_ms += offset_ms;
+            segment.end_ms += offset_ms;
+            self.segments.push(segment);
+        }
+
+        self.committed = join_text(&self.committed, &stripped);
+    }
+
+    /// Whisper keeps the end of an overlong prompt, so the recent committed
+    /// text goes last
+    fn prompt(&self) -> String {
+        let start = self
+            .committed
+            .char_indices()
+            .rev()
+            .nth(PROMPT_CHARS.saturating_sub(1))
+            .map(|(i, _)| i)
+            .unwrap_or(0);
+        join_text(&self.initial_prompt, &self.committed[start..])
+    }
+
+    fn update(&self) -> StreamingUpdate {
+        StreamingUpdate {
+            committed: self.committed.clone(),
+            tentative: self.tentative.clone(),
+        }
+    }
+}
+
+fn join_text(a: &str, b: &str) -> String {
+    match (a.is_empty(), b.is_empty()) {
+        (true, _) => b.to_string(),
+        (_, true) => a.to_string(),
+        _ => format!("{} {}", a, b),
+    }
+}
+
+fn join_segments(segments: &[Segment]) -> String {
+    segments
+        .iter()
+        .map(|s| s.text.as_str())
+        .filter(|t| !t.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn normalize_word(word: &str) -> String {
+    word.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Drop words at the start of `text` that repeat the end of `committed`.
+/// The overlap audio is transcribed twice, so the next window usually
+/// begins with the last few committed words.
+fn strip_overlap(committed: &str, text: &str) -> String {
+    let tail: Vec<String> = committed.split_whitespace().map(normalize_word).collect();
+    let words: Vec<&str> = text.split_whitespace().collect();
+
+    let max = MAX_OVERLAP_WORDS.min(tail.len()).min(words.len());
+    let repeated = (1..=max)
+        .rev()
+        .find(|&n| {
+            tail[tail.len() - n..]
+                .iter()
+                .zip(&words[..n])
+                .all(|(a, b)| *a == normalize_word(b))
+        })
+        .unwrap_or(0);
+
+    words[repeated..].join(" ")
+}
+
 // Thread-safe wrapper for the engine
 pub type SharedWhisperEngine = Arc<Mutex<WhisperEngine>>;
 
@@ -175,70 +508,81 @@ pub fn get_available_models() -> Vec<ModelInfo> {
             name: "Tiny".to_string(),
             filename: "ggml-tiny.bin".to_string(),
             size_mb: 75,
-            url: "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-tiny.bin".to_string(),
+            url: "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-tiny.bin"
+                .to_string(),
             description: "Fastest, least accurate. Good for testing.".to_string(),
         },
         ModelInfo {
             name: "Tiny (English)".to_string(),
             filename: "ggml-tiny.en.bin".to_string(),
             size_mb: 75,
-            url: "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-tiny.en.bin".to_string(),
+            url: "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-tiny.en.bin"
+                .to_string(),
             description: "Tiny model, English only. Faster than multilingual.".to_string(),
         },
         ModelInfo {
             name: "Base".to_string(),
             filename: "ggml-base.bin".to_string(),
             size_mb: 142,
-            url: "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-base.bin".to_string(),
+            url: "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-base.bin"
+                .to_string(),
             description: "Good balance of speed and accuracy.".to_string(),
         },
         ModelInfo {
             name: "Base (English)".to_string(),
             filename: "ggml-base.en.bin".to_string(),
             size_mb: 142,
-            url: "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-base.en.bin".to_string(),
+            url: "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-base.en.bin"
+                .to_string(),
             description: "Base model, English only.".to_string(),
         },
         ModelInfo {
             name: "Small".to_string(),
             filename: "ggml-small.bin".to_string(),
             size_mb: 466,
-            url: "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-small.bin".to_string(),
+            url: "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-small.bin"
+                .to_string(),
             description: "Good accuracy, moderate speed.".to_string(),
         },
         ModelInfo {
             name: "Small (English)".to_string(),
             filename: "ggml-small.en.bin".to_string(),
             size_mb: 466,
-            url: "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-small.en.bin".to_string(),
+            url: "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-small.en.bin"
+                .to_string(),
             description: "Small model, English only.".to_string(),
         },
         ModelInfo {
             name: "Medium".to_string(),
             filename: "ggml-medium.bin".to_string(),
             size_mb: 1500,
-            url: "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-medium.bin".to_string(),
+            url: "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-medium.bin"
+                .to_string(),
             description: "High accuracy, slower. Recommended for quality.".to_string(),
         },
         ModelInfo {
             name: "Medium (English)".to_string(),
             filename: "ggml-medium.en.bin".to_string(),
             size_mb: 1500,
-            url: "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-medium.en.bin".to_string(),
+            url: "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-medium.en.bin"
+                .to_string(),
             description: "Medium model, English only.".to_string(),
         },
         ModelInfo {
             name: "Large-v3".to_string(),
             filename: "ggml-large-v3.bin".to_string(),
             size_mb: 3100,
-            url: "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-large-v3.bin".to_string(),
+            url: "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-large-v3.bin"
+                .to_string(),
             description: "Best accuracy, slowest. Requires GPU for real-time.".to_string(),
         },
         ModelInfo {
             name: "Large-v3 Turbo".to_string(),
             filename: "ggml-large-v3-turbo.bin".to_string(),
             size_mb: 1600,
-            url: "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-large-v3-turbo.bin".to_string(),
+            url:
+                "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-large-v3-turbo.bin"
+                    .to_string(),
             description: "Large v3 optimized for speed. Great balance.".to_string(),
         },
     ]
@@ -249,9 +593,343 @@ pub fn get_models_directory() -> PathBuf {
         .unwrap_or_else(|| PathBuf::from("."))
         .join("hyprwhisper")
         .join("models");
-    
+
     // Ensure directory exists
     std::fs::create_dir_all(&data_dir).ok();
-    
+
     data_dir
 }
+
+pub fn get_downloaded_models() -> Vec<String> {
+    let models_dir = get_models_directory();
+    std::fs::read_dir(&models_dir)
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .filter_map(|e| {
+                    let name = e.file_name().to_string_lossy().to_string();
+                    if name.ends_with(".bin") {
+                        Some(name)
+                    } else {
+                        None
+                    }
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Find a known model by filename or display name (case-insensitive)
+pub fn find_model(name: &str) -> Option<ModelInfo> {
+    let name = name.to_lowercase();
+    get_available_models().into_iter().find(|m| {
+        m.filename.to_lowercase() == name
+            || m.name.to_lowercase() == name
+            || m.filename.to_lowercase() == format!("ggml-{}.bin", name)
+    })
+}
+
+/// Download a model into the models directory, reporting
+/// `(downloaded, total)` bytes as chunks arrive. Already-present models are skipped.
+pub async fn download_model(
+    model: &ModelInfo,
+    mut on_progress: impl FnMut(u64, u64),
+) -> Result<(), String> {
+    use futures_util::StreamExt;
+    use tokio::io::AsyncWriteExt;
+
+    let models_dir = get_models_directory();
+    let model_path = models_dir.join(&model.filename);
+
+    if model_path.exists() {
+        return Ok(());
+    }
+
+    // Create a temp file for downloading
+    let temp_path = model_path.with_extension("bin.part");
+
+    let client = reqwest::Client::new();
+    let response = client
+        .get(&model.url)
+        .send()
+        .await
+        .map_err(|e| format!("Download failed: {}", e))?;
+
+    let total_size = response.content_length().unwrap_or(0);
+    let mut downloaded: u64 = 0;
+
+    let mut file = tokio::fs::File::create(&temp_path)
+        .await
+        .map_err(|e| format!("Failed to create file: {}", e))?;
+
+    let mut stream = response.bytes_stream();
+
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|e| format!("Download error: {}", e))?;
+
+        file.write_all(&chunk)
+            .await
+            .map_err(|e| format!("Write error: {}", e))?;
+
+        downloaded += chunk.len() as u64;
+        on_progress(downloaded, total_size);
+    }
+
+    file.sync_all()
+        .await
+        .map_err(|e| format!("Sync error: {}", e))?;
+
+    // Rename temp file to final name
+    tokio::fs::rename(&temp_path, &model_path)
+        .await
+        .map_err(|e| format!("Rename error: {}", e))?;
+
+    Ok(())
+}
+
+/// Path of `filename` in the models directory. Names come from the CLI and
+/// the control socket, so anything but a plain file name is refused.
+pub fn model_path(filename: &str) -> Result<PathBuf, String> {
+    if !is_plain_file_name(filename) {
+        return Err(format!("Invalid model name '{}'", filename));
+    }
+    Ok(get_models_directory().join(filename))
+}
+
+/// A single path component other than `.` and `..`
+fn is_plain_file_name(name: &str) -> bool {
+    Path::new(name).file_name() == Some(std::ffi::OsStr::new(name))
+}
+
+pub fn delete_model(filename: &str) -> Result<(), String> {
+    let model_path = model_path(filename)?;
+    if model_path.exists() {
+        std::fs::remove_file(&model_path).map_err(|e| format!("Failed to delete model: {}", e))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(start_ms: i64, end_ms: i64, text: &str) -> Segment {
+        Segment {
+            start_ms,
+            end_ms,
+            text: text.to_string(),
+            probability: 1.0,
+            tokens: Vec::new(),
+        }
+    }
+
+    fn transcriber_with_window(ms: usize) -> StreamingTranscriber {
+        let mut transcriber = StreamingTranscriber::new(WHISPER_SAMPLE_RATE, String::new());
+        transcriber.window = vec![0.0; ms_to_samples(ms)];
+        transcriber
+    }
+
+    #[test]
+    fn model_names_must_be_plain_file_names() {
+        assert!(is_plain_file_name("ggml-base.bin"));
+        assert!(is_plain_file_name("my model..bin"));
+
+        for name in [
+            "",
+            ".",
+            "..",
+            "../../.bashrc",
+            "/etc/passwd",
+            "sub/model.bin",
+            "model.bin/",
+        ] {
+            assert!(!is_plain_file_name(name), "{:?}", name);
+        }
+        assert!(delete_model("../settings.json").is_err());
+    }
+
+    #[test]
+    fn join_text_skips_empty_sides() {
+        assert_eq!(join_text("", ""), "");
+        assert_eq!(join_text("one", ""), "one");
+        assert_eq!(join_text("", "two"), "two");
+        assert_eq!(join_text("one", "two"), "one two");
+    }
+
+    #[test]
+    fn join_segments_skips_empty_text() {
+        let segments = [
+            segment(0, 1000, "one"),
+            segment(1000, 1500, ""),
+            segment(1500, 2000, "two"),
+        ];
+        assert_eq!(join_segments(&segments), "one two");
+    }
+
+    #[test]
+    fn strip_overlap_drops_the_repeated_words() {
+        let committed = "the quick brown fox";
+        // Partial overlap
+        assert_eq!(
+            strip_overlap(committed, "brown fox jumps over"),
+            "jumps over"
+        );
+        // The whole text repeats the committed tail
+        assert_eq!(strip_overlap(committed, "quick brown fox"), "");
+        // No overlap
+        assert_eq!(strip_overlap(committed, "jumps over"), "jumps over");
+        assert_eq!(strip_overlap("", "jumps over"), "jumps over");
+        assert_eq!(strip_overlap(committed, ""), "");
+    }
+
+    #[test]
+    fn strip_overlap_ignores_case_and_punctuation() {
+        assert_eq!(
+            strip_overlap("Hello, World.", "world! How are you"),
+            "How are you"
+        );
+        assert_eq!(strip_overlap("it's done", "Its done, finally"), "finally");
+    }
+
+    #[test]
+    fn strip_overlap_prefers_the_longest_match_and_joins_with_single_spaces() {
+        // "a" alone would also match; the three-word repeat wins
+        assert_eq!(strip_overlap("a b a", "a b a  c\n d"), "c d");
+        // A repeat that isn't at the end of `committed` is not an overlap
+        assert_eq!(
+            strip_overlap("one two three", "one two four"),
+            "one two four"
+        );
+    }
+
+    #[test]
+    fn strip_overlap_looks_back_at_most_max_overlap_words() {
+        let words: Vec<String> = (0..=MAX_OVERLAP_WORDS).map(|n| n.to_string()).collect();
+        let committed = words.join(" ");
+        let repeated = format!("{} next", committed);
+        assert_eq!(strip_overlap(&committed, &repeated), repeated);
+
+        let tail = words[1..].join(" ");
+        assert_eq!(strip_overlap(&committed, &format!("{} next", tail)), "next");
+    }
+
+    #[test]
+    fn a_short_window_stays_tentative() {
+        let mut transcriber = transcriber_with_window(5_000);
+        transcriber.absorb(&[
+            segment(0, 2000, "Hello there."),
+            segment(2000, 4000, "How are"),
+        ]);
+
+        let update = transcriber.update();
+        assert_eq!(update.committed, "");
+        assert_eq!(update.tentative, "Hello there. How are");
+        assert_eq!(transcriber.window.len(), ms_to_samples(5_000));
+    }
+
+    #[test]
+    fn a_long_window_commits_all_but_its_last_segment() {
+        let mut transcriber = transcriber_with_window(COMMIT_WINDOW_MS + 1_000);
+        transcriber.absorb(&[
+            segment(0, 4000, "First sentence."),
+            segment(4000, 8000, "Second sentence."),
+            segment(8000, 11000, "Still going"),
+        ]);
+
+        assert_eq!(transcriber.committed, "First sentence. Second sentence.");
+        assert_eq!(transcriber.tentative, "Still going");
+        // Cut at the last committed segment, keeping the overlap before it
+        assert_eq!(
+            transcriber.window.len(),
+            ms_to_samples(COMMIT_WINDOW_MS + 1_000 - (8000 - OVERLAP_MS))
+        );
+    }
+
+    #[test]
+    fn the_next_window_does_not_repeat_the_overlap() {
+        let mut transcriber = transcriber_with_window(COMMIT_WINDOW_MS);
+        transcriber.absorb(&[
+            segment(0, 9000, "one two three"),
+            segment(9000, 10000, "four"),
+        ]);
+        assert_eq!(transcriber.committed, "one two three");
+
+        // The overlap audio is transcribed again at the start of the next window
+        transcriber.window = vec![0.0; ms_to_samples(COMMIT_WINDOW_MS)];
+        transcriber.absorb(&[
+            segment(0, 2000, "Three, four five."),
+            segment(2000, 3000, "six"),
+        ]);
+        assert_eq!(transcriber.committed, "one two three four five.");
+        assert_eq!(transcriber.tentative, "six");
+        assert_eq!(transcriber.update().text(), "one two three four five. six");
+
+        // Timed from the start of the session, the second window began at 8s
+        let timings: Vec<_> = transcriber
+            .segments
+            .iter()
+            .map(|s| (s.start_ms, s.end_ms, s.text.as_str()))
+            .collect();
+        assert_eq!(
+            timings,
+            [(0, 9000, "one two three"), (8000, 10000, "four five.")]
+        );
+    }
+
+    #[test]
+    fn a_segment_that_only_repeats_the_overlap_is_not_kept() {
+        let mut transcriber = transcriber_with_window(COMMIT_WINDOW_MS);
+        transcriber.commit(&[segment(0, 3000, "one two three")]);
+        transcriber.commit(&[
+            segment(0, 500, "two three"),
+            segment(500, 1500, ""),
+            segment(1500, 3000, "four"),
+        ]);
+
+        assert_eq!(transcriber.committed, "one two three four");
+        let texts: Vec<_> = transcriber
+            .segments
+            .iter()
+            .map(|s| s.text.as_str())
+            .collect();
+        assert_eq!(texts, ["one two three", "four"]);
+    }
+
+    #[test]
+    fn a_full_window_commits_everything() {
+        let mut transcriber = transcriber_with_window(MAX_WINDOW_MS);
+        transcriber.absorb(&[segment(0, 12000, "one"), segment(12000, 24000, "two")]);
+
+        assert_eq!(transcriber.committed, "one two");
+        assert_eq!(transcriber.tentative, "");
+        assert_eq!(
+            transcriber.window.len(),
+            ms_to_samples(MAX_WINDOW_MS - (24000 - OVERLAP_MS))
+        );
+    }
+
+    #[test]
+    fn a_full_window_without_speech_is_dropped_but_for_the_overlap() {
+        let mut transcriber = transcriber_with_window(MAX_WINDOW_MS);
+        transcriber.absorb(&[]);
+
+        assert_eq!(transcriber.update().text(), "");
+        assert_eq!(transcriber.window.len(), ms_to_samples(OVERLAP_MS));
+    }
+
+    #[test]
+    fn the_prompt_ends_with_recent_committed_text() {
+        let mut transcriber = StreamingTranscriber::new(WHISPER_SAMPLE_RATE, "Vocab.".into());
+        assert_eq!(transcriber.prompt(), "Vocab.");
+
+        transcriber.commit(&[segment(0, 1000, "short")]);
+        assert_eq!(transcriber.prompt(), "Vocab. short");
+
+        let long = "é".repeat(PROMPT_CHARS) + " end";
+        transcriber.commit(&[segment(1000, 2000, &long)]);
+        let prompt = transcriber.prompt();
+        assert!(prompt.starts_with("Vocab. "));
+        assert!(prompt.ends_with(" end"));
+        assert_eq!(prompt.chars().count(), "Vocab. ".len() + PROMPT_CHARS);
+    }
+}